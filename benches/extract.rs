@@ -0,0 +1,177 @@
+//! Benchmarks of parsing and extracting Rust sources, run with `cargo bench`
+//!
+//! Sources are `data/error.rs` (about a thousand lines) repeated to get
+//! small, medium and large inputs, so numbers are comparable between runs.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rust_hero::query::{Extractor, Language, ParserPool};
+use tree_sitter::Parser;
+
+/// A representative Rust file
+const ERROR_RS: &str = include_str!("../data/error.rs");
+
+/// Queries from cheap to expensive
+const QUERIES: &[(&str, &str)] = &[
+    ("simple", "(function_item (identifier) @id)"),
+    (
+        "nested",
+        "(impl_item type: (_) @type body: (declaration_list (function_item name: (identifier) @method) @function))",
+    ),
+    (
+        "predicates",
+        "((identifier) @id (#match? @id \"^[a-z]+_[a-z_]+$\")) ((call_expression function: (identifier) @callee) @call (#eq? @callee \"new\"))",
+    ),
+];
+
+/// Sources of different sizes, from a hundred lines to ten thousand
+fn sources() -> Vec<(String, Vec<u8>)> {
+    [("small", 0), ("medium", 1), ("large", 10)]
+        .iter()
+        .map(|(name, times)| {
+            let source = if *times == 0 {
+                // the first hundred lines only
+                ERROR_RS.lines().take(100).collect::<Vec<_>>().join("\n")
+            } else {
+                ERROR_RS.repeat(*times)
+            };
+            (name.to_string(), source.into_bytes())
+        })
+        .collect()
+}
+
+/// Extractor of one of `QUERIES`
+fn extractor(raw: &str) -> Extractor {
+    let lang = Language::Rust;
+    Extractor::new(lang, lang.parse_query(raw).unwrap())
+}
+
+fn parse(c: &mut Criterion) {
+    let extractor = extractor(QUERIES[0].1);
+    let mut parser = Parser::new();
+
+    let mut group = c.benchmark_group("parse");
+    for (name, source) in sources() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |b, source| {
+            b.iter(|| extractor.parse(source, &mut parser).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn extract(c: &mut Criterion) {
+    let mut parser = Parser::new();
+
+    let mut group = c.benchmark_group("extract_from_text");
+    for (query_name, raw) in QUERIES {
+        let extractor = extractor(raw);
+        for (name, source) in sources() {
+            group.throughput(Throughput::Bytes(source.len() as u64));
+            group.bench_with_input(BenchmarkId::new(*query_name, name), &source, |b, source| {
+                b.iter(|| {
+                    extractor
+                        .extract_from_text(None, source, &mut parser)
+                        .unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn matches(c: &mut Criterion) {
+    let mut parser = Parser::new();
+
+    // matching alone, the tree being parsed once beforehand
+    let mut group = c.benchmark_group("matches_iter");
+    for (query_name, raw) in QUERIES {
+        let extractor = extractor(raw);
+        for (name, source) in sources() {
+            let tree = extractor.parse(&source, &mut parser).unwrap();
+            group.throughput(Throughput::Bytes(source.len() as u64));
+            group.bench_with_input(BenchmarkId::new(*query_name, name), &source, |b, source| {
+                b.iter(|| {
+                    extractor
+                        .matches_iter(&tree, source, &mut extractor.query_cursors())
+                        .count()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn parsers(c: &mut Criterion) {
+    let extractor = extractor(QUERIES[0].1);
+    let source = sources().remove(0).1;
+
+    // what reusing a configured parser saves on many small files
+    let mut group = c.benchmark_group("parser_reuse");
+    group.bench_function("fresh", |b| {
+        b.iter(|| {
+            extractor
+                .extract_from_text(None, &source, &mut Parser::new())
+                .unwrap()
+        })
+    });
+    group.bench_function("pool", |b| {
+        let mut pool = ParserPool::new();
+        b.iter(|| {
+            let parser = pool.get(Language::Rust).unwrap();
+            extractor.extract_from_text(None, &source, parser).unwrap()
+        })
+    });
+    group.bench_function("thread_local", |b| {
+        b.iter(|| extractor.extract_pooled(None, &source).unwrap())
+    });
+    group.finish();
+}
+
+fn cursors(c: &mut Criterion) {
+    let extractor = extractor(QUERIES[0].1);
+    let source = sources().remove(0).1;
+    let mut parser = Parser::new();
+
+    // what reusing the query cursors saves on many small files
+    let mut group = c.benchmark_group("cursor_reuse");
+    group.bench_function("fresh", |b| {
+        b.iter(|| {
+            extractor
+                .extract_from_text(None, &source, &mut parser)
+                .unwrap()
+        })
+    });
+    group.bench_function("reused", |b| {
+        let mut cursors = extractor.query_cursors();
+        b.iter(|| {
+            extractor
+                .extract_with_cursors(None, &source, &mut parser, &mut cursors)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn borrowed(c: &mut Criterion) {
+    let mut parser = Parser::new();
+
+    // what borrowing the captured text saves over copying it
+    let mut group = c.benchmark_group("borrowed_text");
+    let extractor = extractor(QUERIES[2].1);
+    for (name, source) in sources() {
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::new("owned", &name), &source, |b, source| {
+            b.iter(|| {
+                extractor
+                    .extract_from_text(None, source, &mut parser)
+                    .unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("borrowed", &name), &source, |b, source| {
+            b.iter(|| extractor.extract_borrowed(source, &mut parser).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, extract, matches, parsers, cursors, borrowed);
+criterion_main!(benches);
@@ -1,3 +1,4 @@
+use git2::build::CheckoutBuilder;
 use git2::Repository;
 use std::path::{Path, PathBuf};
 
@@ -10,31 +11,110 @@ fn clone<P: AsRef<Path>>(url: &str, path: P) -> std::io::Result<()> {
     };
     Ok(())
 }
-fn main() {
-    // vendor download
-    if !Path::new("vendor/tree-sitter-rust/src").exists() {
+
+/// Check out `rev` (a tag or a commit) in the repository at `path`
+fn checkout<P: AsRef<Path>>(path: P, rev: &str) -> Result<(), git2::Error> {
+    let repo = Repository::open(path)?;
+    let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))?;
+    repo.set_head_detached(commit.id())
+}
+
+/// Download the grammar `repo` of the tree-sitter organization into `vendor/<repo>`, at `rev`
+///
+/// tree-sitter 0.20 only loads parsers up to ABI 14, and newer grammars
+/// are generated for later ABIs, so every grammar is pinned to a revision
+/// known to work with it. Already downloaded grammars are checked out at
+/// `rev` too, in case they were cloned before the pin.
+fn vendor(repo: &str, rev: &str) {
+    let path = Path::new("vendor").join(repo);
+    if !path.exists() {
         clone(
-            "https://github.com/tree-sitter/tree-sitter-rust.git",
-            "vendor/tree-sitter-rust",
+            &format!("https://github.com/tree-sitter/{}.git", repo),
+            &path,
         )
         .ok();
     }
+    if let Err(e) = checkout(&path, rev) {
+        panic!("failed to check out {} of {}: {}", rev, repo, e);
+    }
+}
+
+/// Compile the parser and, if the grammar has one, the external scanner found in `src_dir`
+fn compile(name: &str, src_dir: &Path) {
+    let parser = src_dir.join("parser.c");
+    println!("cargo:rerun-if-changed={}", parser.display());
+    cc::Build::new()
+        .include(src_dir)
+        .warnings(false)
+        .file(&parser)
+        .compile(name);
+
+    let scanner = src_dir.join("scanner.c");
+    if scanner.exists() {
+        println!("cargo:rerun-if-changed={}", scanner.display());
+        cc::Build::new()
+            .include(src_dir)
+            .warnings(false)
+            .file(&scanner)
+            .compile(&format!("{}_scanner", name.replace('-', "_")));
+    }
+
+    // older grammars have their scanner written in C++
+    let scanner = src_dir.join("scanner.cc");
+    if scanner.exists() {
+        println!("cargo:rerun-if-changed={}", scanner.display());
+        cc::Build::new()
+            .cpp(true)
+            .include(src_dir)
+            .warnings(false)
+            .file(&scanner)
+            .compile(&format!("{}_scanner", name.replace('-', "_")));
+    }
+}
+
+fn main() {
+    // vendor download
+    vendor("tree-sitter-rust", "v0.21.2");
+    vendor("tree-sitter-python", "v0.20.4");
+    vendor("tree-sitter-javascript", "v0.20.1");
+    vendor("tree-sitter-typescript", "v0.20.3");
+    vendor("tree-sitter-go", "v0.20.0");
+    vendor("tree-sitter-c", "v0.20.6");
+    vendor("tree-sitter-cpp", "v0.20.3");
 
     println!("cargo:rerun-if-changed=build.rs");
     // rust
     let rust_dir: PathBuf = ["vendor", "tree-sitter-rust", "src"].iter().collect();
+    compile("tree-sitter-rust", &rust_dir);
 
-    println!("cargo:rerun-if-changed=vendor/tree-sitter-rust/src/parser.c");
-    cc::Build::new()
-        .include(&rust_dir)
-        .warnings(false)
-        .file(rust_dir.join("parser.c"))
-        .compile("tree-sitter-rust");
+    // python
+    let python_dir: PathBuf = ["vendor", "tree-sitter-python", "src"].iter().collect();
+    compile("tree-sitter-python", &python_dir);
 
-    println!("cargo:rerun-if-changed=vendor/tree-sitter-rust/src/scanner.c");
-    cc::Build::new()
-        .include(&rust_dir)
-        .warnings(false)
-        .file(rust_dir.join("scanner.c"))
-        .compile("tree_sitter_rust_scanner");
+    // javascript
+    let javascript_dir: PathBuf = ["vendor", "tree-sitter-javascript", "src"].iter().collect();
+    compile("tree-sitter-javascript", &javascript_dir);
+
+    // typescript and tsx, two grammars in the same repository
+    let typescript_dir: PathBuf = ["vendor", "tree-sitter-typescript", "typescript", "src"]
+        .iter()
+        .collect();
+    compile("tree-sitter-typescript", &typescript_dir);
+    let tsx_dir: PathBuf = ["vendor", "tree-sitter-typescript", "tsx", "src"]
+        .iter()
+        .collect();
+    compile("tree-sitter-tsx", &tsx_dir);
+
+    // go
+    let go_dir: PathBuf = ["vendor", "tree-sitter-go", "src"].iter().collect();
+    compile("tree-sitter-go", &go_dir);
+
+    // c
+    let c_dir: PathBuf = ["vendor", "tree-sitter-c", "src"].iter().collect();
+    compile("tree-sitter-c", &c_dir);
+
+    // c++
+    let cpp_dir: PathBuf = ["vendor", "tree-sitter-cpp", "src"].iter().collect();
+    compile("tree-sitter-cpp", &cpp_dir);
 }
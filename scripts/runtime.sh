@@ -1,4 +1,11 @@
 #!/bin/bash
 echo "Downloading vendor for tree-grepper"
+# pinned to the revisions build.rs checks out, which tree-sitter 0.20 can load
 cd vendor
-git clone https://github.com/tree-sitter/tree-sitter-rust.git
+git clone --branch v0.21.2 https://github.com/tree-sitter/tree-sitter-rust.git
+git clone --branch v0.20.4 https://github.com/tree-sitter/tree-sitter-python.git
+git clone --branch v0.20.1 https://github.com/tree-sitter/tree-sitter-javascript.git
+git clone --branch v0.20.3 https://github.com/tree-sitter/tree-sitter-typescript.git
+git clone --branch v0.20.0 https://github.com/tree-sitter/tree-sitter-go.git
+git clone --branch v0.20.6 https://github.com/tree-sitter/tree-sitter-c.git
+git clone --branch v0.20.3 https://github.com/tree-sitter/tree-sitter-cpp.git
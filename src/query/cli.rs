@@ -106,7 +106,7 @@ impl Invocation {
                 Arg::new("FORMAT")
                 .long("format")
                 .short('f')
-                .possible_values(&["classes", "lines", "json", "json-lines", "pretty-json"])
+                .possible_values(&["classes", "lines", "json", "json-lines", "pretty-json", "quickfix"])
                 .default_value("classes")
                 .help("what format should we output lines in?")
             )
@@ -217,6 +217,8 @@ pub enum QueryFormat {
     JsonLines,
     /// Display extracted information in PrettyJson
     PrettyJson,
+    /// Display extracted information as Vim/Emacs quickfix lines
+    Quickfix,
 }
 
 impl FromStr for QueryFormat {
@@ -229,6 +231,7 @@ impl FromStr for QueryFormat {
             "json" => Ok(QueryFormat::Json),
             "json-lines" => Ok(QueryFormat::JsonLines),
             "pretty-json" => Ok(QueryFormat::PrettyJson),
+            "quickfix" => Ok(QueryFormat::Quickfix),
             _ => bail!("unknown format. See --help for valid formats."),
         }
     }
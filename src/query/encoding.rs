@@ -0,0 +1,40 @@
+use encoding_rs::{Encoding, WINDOWS_1252};
+use std::borrow::Cow;
+
+/// How source files are decoded before parsing, see `Extractor::with_encoding`
+///
+/// Sources which aren't UTF-8 are converted to UTF-8 first, so the positions
+/// and byte offsets of the matches are those of the decoded UTF-8 text, not
+/// of the original bytes. Malformed sequences are replaced with `U+FFFD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceEncoding {
+    /// Sources are taken as UTF-8 as they are (the default)
+    Utf8,
+    /// Sources starting with a BOM are decoded as UTF-8 or UTF-16 accordingly,
+    /// the others are taken as UTF-8 if valid, and as Windows-1252 (a superset
+    /// of Latin-1) otherwise
+    Detect,
+    /// Sources are always decoded with this encoding, such as `encoding_rs::UTF_16LE`
+    Force(&'static Encoding),
+}
+
+impl SourceEncoding {
+    /// Decode `source` to UTF-8, without copying it when it already is
+    pub(crate) fn decode<'source>(&self, source: &'source [u8]) -> Cow<'source, [u8]> {
+        let decoded = match self {
+            SourceEncoding::Utf8 => return Cow::Borrowed(source),
+            // `decode` sniffs the BOM itself, and removes it
+            SourceEncoding::Detect => match Encoding::for_bom(source) {
+                Some((encoding, _)) => encoding.decode(source).0,
+                None if std::str::from_utf8(source).is_ok() => return Cow::Borrowed(source),
+                None => WINDOWS_1252.decode(source).0,
+            },
+            SourceEncoding::Force(encoding) => encoding.decode_with_bom_removal(source).0,
+        };
+
+        match decoded {
+            Cow::Borrowed(text) => Cow::Borrowed(text.as_bytes()),
+            Cow::Owned(text) => Cow::Owned(text.into_bytes()),
+        }
+    }
+}
@@ -1,31 +1,103 @@
-use crate::query::Language;
-use anyhow::{Context, Result};
+use crate::query::language;
+#[cfg(feature = "encoding")]
+use crate::query::SourceEncoding;
+use crate::query::{Language, ParserPool, QueryParseError, WalkOptions};
+use anyhow::{bail, Context, Error, Result};
+use ignore::types::Types;
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use regex::Regex;
+use serde::de::{self, Deserializer};
 use serde::ser::{SerializeStruct, Serializer};
-use serde::Serialize;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{self, Display};
 use std::fs;
-use std::path::{Path, PathBuf};
-use tree_sitter::{Parser, Point, Query, QueryCursor};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::Arc;
+use std::time::Duration;
+use tree_sitter::{
+    InputEdit, Node, Parser, Point, Query, QueryCursor, QueryMatch, QueryPredicateArg, Tree,
+};
 
 /// Extractor for extracting syntax information of program
-#[derive(Debug)]
+///
+/// Cloning is cheap: the compiled queries are shared between the clones, so
+/// every worker thread can own one without parsing the query again.
+///
+/// An Extractor is also `Send + Sync` and never mutated while extracting, so
+/// a single one can be shared by reference across threads as well; only the
+/// `Parser` has to be per thread.
+#[derive(Debug, Clone)]
 pub struct Extractor {
-    /// Language configuration
-    language: Language,
+    /// Language configuration, `None` for a grammar given by the user
+    language: Option<Language>,
     /// Language for tree_sitter
     ts_language: tree_sitter::Language,
-    /// Tree_sitter query: a set of patterns that match nodes in a syntax tree.
-    query: Query,
-    /// Names of the captures used in the query.
+    /// Name of the language shown as `file_type` of the extracted files
+    file_type: String,
+    /// Tree_sitter queries: sets of patterns that match nodes in a syntax tree.
+    queries: Vec<Arc<Query>>,
+    /// Names of the captures used in the queries, one query after another.
     captures: Vec<String>,
+    /// Index in `captures` of the first capture of each query
+    capture_offsets: Vec<usize>,
     /// Ignored names with '_'
     ignores: HashSet<usize>,
+    /// Whether coordinates of matches are shown one-based or not
+    one_based: bool,
+    /// Most chars of captured text shown, if truncated
+    max_text_len: Option<usize>,
+    /// Whether captured text is shown on a single line
+    single_line_text: bool,
+    /// Whether matches are serialized with their escaped text too
+    escaped_text: bool,
+    /// Whether the end of matches is shown too
+    end_position: bool,
+    /// Whether invalid UTF-8 in captured text is replaced instead of failing
+    lossy_text: bool,
+    /// Whether single-line captures leave out the `\r` of a CRLF line end
+    trim_cr: bool,
+    /// Whether captured text is left out of the matches
+    skip_text: bool,
+    /// Longest time a parse may take
+    timeout: Option<Duration>,
+    /// Indices in `captures` of the only captures kept, if filtered
+    capture_filter: Option<HashSet<usize>>,
+    /// Regex the captured text must contain, and the only capture it applies to
+    text_filter: Option<(Regex, Option<String>)>,
+    /// Most matches extracted from one file
+    max_matches: Option<usize>,
+    /// Most in-progress matches of a query cursor
+    match_limit: Option<u32>,
+    /// Name of the capture labelling the other captures of its query match
+    label_capture: Option<String>,
+    /// Whether only the first kept capture of each query match is extracted
+    first_capture_only: bool,
+    /// Whether the paths of extracted files are normalized
+    normalize_paths: bool,
+    /// Directory the paths of extracted files are made relative to
+    base_dir: Option<PathBuf>,
+    /// Whether source files are memory-mapped instead of read
+    #[cfg(feature = "mmap")]
+    mmap: bool,
+    /// How sources are decoded before parsing
+    #[cfg(feature = "encoding")]
+    encoding: SourceEncoding,
 }
 
 impl Extractor {
     /// Build a new Extractor
     ///
+    /// Captures whose name starts with `_` are left out of the matches, see
+    /// `with_ignore_underscore_captures`.
+    ///
     /// # Arguments
     ///
     /// * `language` - the language of source code
@@ -51,53 +123,243 @@ impl Extractor {
     /// # }
     /// ```
     pub fn new(language: Language, query: Query) -> Extractor {
-        let captures = query.capture_names().to_vec();
+        Extractor::from_queries(language, vec![query])
+    }
 
-        let mut ignores = HashSet::default();
-        captures.iter().enumerate().for_each(|(i, name)| {
-            if name.starts_with('_') {
-                ignores.insert(i);
-            }
-        });
+    /// Build a new Extractor running several queries over the same parsed tree
+    ///
+    /// Every file is parsed once no matter how many queries there are. The
+    /// `query_index` of each `ExtractedMatch` tells which query produced it.
+    ///
+    /// # Arguments
+    ///
+    /// * `language` - the language of source code
+    ///
+    /// * `queries` - tree_sitter queries
+    ///
+    /// # Returns
+    ///
+    /// * `Extractor` object
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let functions = lang.parse_query("(function_item) @item").unwrap();
+    /// let structs = lang.parse_query("(struct_item) @item").unwrap();
+    /// let extractor = Extractor::from_queries(lang, vec![functions, structs]);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"struct S;\nfn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let kinds: Vec<_> = extracted
+    ///     .matches
+    ///     .iter()
+    ///     .map(|m| (m.query_index, m.kind()))
+    ///     .collect();
+    /// assert_eq!(kinds, vec![(0, "function_item"), (1, "struct_item")]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_queries(language: Language, queries: Vec<Query>) -> Extractor {
+        Extractor::from_parts(
+            Some(language),
+            language.language(),
+            language.to_string(),
+            queries,
+        )
+    }
+
+    /// Build a new Extractor for a tree_sitter grammar the `Language` enum doesn't bundle
+    ///
+    /// Brings any grammar (Zig, Nix...) to the same extraction machinery.
+    /// `label` is shown as the `file_type` of the extracted files. Since the
+    /// file extensions of the grammar aren't known, the Extractor can't walk
+    /// directories nor be chosen by an `ExtractorChooser`; give it the files
+    /// one by one instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// // any tree_sitter::Language works, such as the one of a grammar crate
+    /// let grammar = Language::Rust.language();
+    /// let extractor = Extractor::from_raw(grammar, "(function_item (identifier) @id)", "my-rust")?;
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extractor.known_language(), None);
+    /// assert_eq!(extracted.file_type, "my-rust");
+    /// assert_eq!(extracted.matches[0].text, "main");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_raw(
+        ts_language: tree_sitter::Language,
+        query_src: &str,
+        label: &str,
+    ) -> Result<Extractor> {
+        let query = Query::new(ts_language, query_src)
+            .map_err(|err| QueryParseError::new(err, query_src, None))?;
+
+        Ok(Extractor::from_parts(
+            None,
+            ts_language,
+            label.to_string(),
+            vec![query],
+        ))
+    }
+
+    /// Build a new Extractor with the default options
+    fn from_parts(
+        language: Option<Language>,
+        ts_language: tree_sitter::Language,
+        file_type: String,
+        queries: Vec<Query>,
+    ) -> Extractor {
+        let mut captures = Vec::new();
+        let mut capture_offsets = Vec::with_capacity(queries.len());
+        for query in &queries {
+            capture_offsets.push(captures.len());
+            captures.extend_from_slice(query.capture_names());
+        }
+
+        let ignores = underscore_captures(&captures);
 
         Extractor {
-            ts_language: (&language).language(),
             language,
-            query,
+            ts_language,
+            file_type,
+            queries: queries.into_iter().map(Arc::new).collect(),
             captures,
+            capture_offsets,
             ignores,
+            one_based: true,
+            max_text_len: None,
+            single_line_text: false,
+            escaped_text: false,
+            end_position: false,
+            lossy_text: false,
+            trim_cr: false,
+            skip_text: false,
+            timeout: None,
+            capture_filter: None,
+            text_filter: None,
+            max_matches: None,
+            match_limit: None,
+            label_capture: None,
+            first_capture_only: false,
+            normalize_paths: false,
+            base_dir: None,
+            #[cfg(feature = "mmap")]
+            mmap: false,
+            #[cfg(feature = "encoding")]
+            encoding: SourceEncoding::Utf8,
         }
     }
 
-    /// Get the language of Extractor
-    pub fn language(&self) -> &Language {
-        &self.language
+    /// Choose whether coordinates of matches are shown one-based (the default) or
+    /// zero-based like tree_sitter and LSP
+    ///
+    /// This only changes the `Display` and serialized output; `ExtractedMatch::start`
+    /// and `ExtractedMatch::end` always hold the raw tree_sitter `Point`.
+    /// Zero-based points are serialized with a `"base": 0` field, so they
+    /// are read back right.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_one_based(false);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.to_string(), "NO FILE:0:3:id:main\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_one_based(mut self, one_based: bool) -> Extractor {
+        self.one_based = one_based;
+        self
     }
 
-    /// Extracted query information from one source file
-    pub fn extract_from_file(
-        &self,
-        path: &Path,
-        parser: &mut Parser,
-    ) -> Result<Option<ExtractedFile>> {
-        let source = fs::read(&path).context("could not read file")?;
-
-        self.extract_from_text(Some(path), &source, parser)
+    /// Truncate the captured text shown to `max_text_len` chars, followed by `…`
+    ///
+    /// Keeps the line-oriented output readable when whole functions are
+    /// captured. Like `with_one_based`, this only changes the `Display` and
+    /// colored output; `ExtractedMatch::text` is always the whole text.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item) @function").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_max_text_len(7);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.to_string(), "NO FILE:1:1:function:fn main…\n");
+    /// assert_eq!(extracted.matches[0].text, "fn main(){}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_max_text_len(mut self, max_text_len: usize) -> Extractor {
+        self.max_text_len = Some(max_text_len);
+        self
     }
 
-    /// Extracted query information from one fragment program
-    ///     
-    /// # Arguments
+    /// Choose whether the captured text shown is collapsed to a single line, see `ExtractedMatch::single_line_text`
     ///
-    /// * `path` - Option: the path of source file
+    /// Like `with_max_text_len`, this only changes the `Display` and colored
+    /// output, and both can be combined: the text is collapsed first.
     ///
-    /// * `source` - fragment program
+    /// # Example
     ///
-    /// * `parser` - tree_sitter Parser
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
     ///
-    /// # Returns
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item) @function").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_single_line_text(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main() {\n    run();\n}", &mut Parser::new())?
+    ///     .unwrap();
     ///
-    /// * `ExtractedFile` object
+    /// assert_eq!(extracted.to_string(), "NO FILE:1:1:function:fn main() { run(); }\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_single_line_text(mut self, single_line_text: bool) -> Extractor {
+        self.single_line_text = single_line_text;
+        self
+    }
+
+    /// Choose whether matches are serialized with a `text_escaped` field next to `text`, see `ExtractedMatch::escaped_text`
+    ///
+    /// Raw JSON of captures spanning several lines or holding control
+    /// characters is hard to read; the escaped text keeps them on one line.
+    /// `text` is always serialized as is.
     ///
     /// # Example
     ///
@@ -107,150 +369,4033 @@ impl Extractor {
     /// use tree_sitter::Parser;
     ///
     /// let lang = Language::Rust;
-    /// let query = lang
-    ///     .parse_query("(function_item (identifier) @id) @function")
+    /// let query = lang.parse_query("(block) @body").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_escaped_text(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main() {\n\trun();\n}", &mut Parser::new())?
     ///     .unwrap();
-    /// let extractor = Extractor::new(lang, query);
-    ///         let extracted = extractor
-    ///        .extract_from_text(None, b"fn main(){println!(\"hello rust_hero\");}", &mut Parser::new())
-    ///        // From Result<Option<ExtractedFile>>
-    ///        .unwrap()
-    ///        // From Option<ExtractedFile>
-    ///        .unwrap();
     ///
-    /// println!("{:?},{:?}，{:?}",extracted.matches.len(),extracted.matches[0].name,extracted.matches[0].text);
-    /// assert_eq!(extracted.matches.len(), 2);
-    /// assert_eq!(extracted.matches[0].name, "function");
-    /// assert_eq!(extracted.matches[0].text, "fn main(){println!(\"hello rust_hero\");}");
+    /// let json = serde_json::to_value(&extracted.matches[0])?;
+    /// assert_eq!(json["text"], "{\n\trun();\n}");
+    /// assert_eq!(json["text_escaped"], "{\\n\\trun();\\n}");
     /// # Ok(())
     /// # }
     /// ```
-    pub fn extract_from_text(
-        &self,
-        path: Option<&Path>,
-        source: &[u8],
-        parser: &mut Parser,
-    ) -> Result<Option<ExtractedFile>> {
-        parser
-            .set_language(self.ts_language)
-            .context("could not set language")?;
-
-        let tree = parser
-            .parse(&source, None)
-            // note: this could be a timeout or cancellation, but we don't set
-            // that so we know it's always a language error. Buuuut we also
-            // always set the language above so if this happens we also know
-            // it's an internal error.
-            .context(
-                "could not parse to a tree. This is an internal error and should be reported.",
-            )?;
-
-        let mut cursor = QueryCursor::new();
-
-        let extracted_matches = cursor
-            .matches(&self.query, tree.root_node(), source)
-            .flat_map(|query_match| query_match.captures)
-            // note: the casts here could potentially break if run on a 16-bit
-            // microcontroller. I don't think this is a huge problem, though,
-            // since even the gnarliest queries I've written have something on
-            // the order of 20 matches. Nowhere close to 2^16!
-            .filter(|capture| !self.ignores.contains(&(capture.index as usize)))
-            .map(|capture| {
-                let name = &self.captures[capture.index as usize];
-                let node = capture.node;
-                let text = match node
-                    .utf8_text(source)
-                    .map(|unowned| unowned.to_string())
-                    .context("could not extract text from capture")
-                {
-                    Ok(text) => text,
-                    Err(problem) => return Err(problem),
-                };
-
-                Ok(ExtractedMatch {
-                    kind: node.kind(),
-                    name,
-                    text,
-                    start: node.start_position(),
-                    end: node.end_position(),
-                })
-            })
-            .collect::<Result<Vec<ExtractedMatch>>>()?;
+    pub fn with_escaped_text(mut self, escaped_text: bool) -> Extractor {
+        self.escaped_text = escaped_text;
+        self
+    }
 
-        if extracted_matches.is_empty() {
-            Ok(None)
-        } else {
-            Ok(Some(ExtractedFile {
-                file: path.map(|p| p.to_owned()),
-                file_type: self.language.to_string(),
-                matches: extracted_matches,
-            }))
-        }
+    /// Choose whether the lines of `Display` show the end of matches too, as `file:row:col-end_row:end_col:name:text`
+    ///
+    /// Editors can select a range instead of jumping to a point. The colored
+    /// output and `ExtractedFile::write_separated` follow it as well.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_end_position(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.to_string(), "NO FILE:1:4-1:8:id:main\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_end_position(mut self, end_position: bool) -> Extractor {
+        self.end_position = end_position;
+        self
     }
-}
 
-/// Extracted query from source file
-#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
-pub struct ExtractedFile<'query> {
-    /// Extracted source file
-    pub file: Option<PathBuf>,
-    /// Language
-    pub file_type: String,
-    /// A set of patterns that match nodes in a syntax tree.
-    pub matches: Vec<ExtractedMatch<'query>>,
-}
+    /// Choose whether captured text that isn't valid UTF-8 is decoded lossily
+    /// (with `U+FFFD` replacement characters) instead of failing the file
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let source = b"// \xff\nfn main(){}";
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(line_comment) @comment").unwrap();
+    ///
+    /// let strict = Extractor::new(lang, query);
+    /// assert!(strict.extract_from_text(None, source, &mut Parser::new()).is_err());
+    ///
+    /// let lossy = strict.with_lossy_text(true);
+    /// let extracted = lossy.extract_from_text(None, source, &mut Parser::new())?.unwrap();
+    /// assert!(extracted.matches[0].text.starts_with("// \u{fffd}"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_lossy_text(mut self, lossy_text: bool) -> Extractor {
+        self.lossy_text = lossy_text;
+        self
+    }
 
-impl<'query> Display for ExtractedFile<'query> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // TODO: is there a better way to do this unwrapping? This implementation
-        // turns non-UTF-8 paths into "NON-UTF8 FILENAME". I don't know exactly
-        // what circumstances that could happen in... maybe we should just wait
-        // for bug reports?
-        let filename = self
-            .file
-            .as_ref()
-            .map(|f| f.to_str().unwrap_or("NON-UTF8 FILENAME"))
-            .unwrap_or("NO FILE");
+    /// Choose whether single-line captures leave out the `\r` ending their line in CRLF files
+    ///
+    /// tree_sitter counts rows on `\n` only, so rows and columns of CRLF
+    /// files are right, but a capture running to the end of a line (a line
+    /// comment, say) takes the `\r` along. With this option its text, `end`
+    /// and `end_byte` stop before the `\r`. Captures spanning several lines
+    /// are kept as they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(line_comment) @comment").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_trim_cr(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"// hello\r\nfn main(){}\r\n", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].text, "// hello");
+    /// assert_eq!((extracted.matches[0].end.column, extracted.matches[0].end_byte), (8, 8));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_trim_cr(mut self, trim_cr: bool) -> Extractor {
+        self.trim_cr = trim_cr;
+        self
+    }
 
-        for extraction in &self.matches {
-            writeln!(
-                f,
-                "{}:{}:{}:{}:{}",
-                filename,
-                extraction.start.row + 1,
-                extraction.start.column + 1,
-                extraction.name,
-                extraction.text
-            )?
-        }
+    /// Choose whether captured text is left out of the matches, leaving their `text` empty
+    ///
+    /// Indexers only after positions can save copying and validating the
+    /// text of every capture: nothing is allocated for it, and invalid
+    /// UTF-8 can't fail the file. The text filter still applies, to the
+    /// source text of the captures. Labels (see `with_label_capture`) are
+    /// empty too, and so is everything shown of the text.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_skip_text(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].text, "");
+    /// assert_eq!((extracted.matches[0].start_byte, extracted.matches[0].end_byte), (3, 7));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_skip_text(mut self, skip_text: bool) -> Extractor {
+        self.skip_text = skip_text;
+        self
+    }
 
-        Ok(())
+    /// Choose whether captures whose name starts with `_` are left out of the matches (the default)
+    ///
+    /// Such captures are usually helpers for predicates, like `@_name` in
+    /// `((identifier) @_name (#eq? @_name "main"))`, but a query may also
+    /// mean them to be shown.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @_id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// assert!(extractor.extract_from_text(None, b"fn main(){}", &mut Parser::new())?.is_none());
+    ///
+    /// let extractor = extractor.with_ignore_underscore_captures(false);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    /// assert_eq!(extracted.matches[0].name, "_id");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_ignore_underscore_captures(mut self, ignore: bool) -> Extractor {
+        self.ignores = if ignore {
+            underscore_captures(&self.captures)
+        } else {
+            HashSet::new()
+        };
+        self
     }
-}
 
-/// Pattern matching nodes in a syntax tree.
-#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
-pub struct ExtractedMatch<'query> {
-    /// Pattern type
-    kind: &'static str,
-    /// Pattern name
-    pub name: &'query str,
-    /// Fragment program
-    pub text: String,
-    /// Start cordinate of current text
-    #[serde(serialize_with = "serialize_point")]
-    pub start: Point,
+    /// Give up parsing a source after `timeout`, so a huge or pathological file can't hang a batch run
+    ///
+    /// A parse that takes too long fails with a `ParseTimeout` error, which
+    /// can be told apart from the other errors with `downcast_ref`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor,ParseTimeout};
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item) @function").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_timeout(Duration::from_secs(5));
+    /// match extractor.extract_from_file(Path::new("data/error.rs"), &mut Parser::new()) {
+    ///     Err(err) if err.downcast_ref::<ParseTimeout>().is_some() => eprintln!("skipped: {}", err),
+    ///     result => println!("{:?}", result?),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_timeout(mut self, timeout: Duration) -> Extractor {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Keep only the captures named in `names`, skipping the others while matching
+    ///
+    /// Handy when a query file shared between tools has more captures than
+    /// needed. Captures starting with `_` stay ignored even when listed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query).with_capture_filter(&["id"]);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches.len(), 1);
+    /// assert_eq!(extracted.matches[0].name, "id");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_capture_filter(mut self, names: &[&str]) -> Extractor {
+        let kept = self
+            .captures
+            .iter()
+            .enumerate()
+            .filter(|(_, capture)| names.contains(&capture.as_str()))
+            .map(|(i, _)| i)
+            .collect();
+        self.capture_filter = Some(kept);
+        self
+    }
+
+    /// Keep only the matches whose text contains a match of `regex`
+    ///
+    /// The regex is compiled once by the caller and searched for anywhere in
+    /// the text, like `Regex::is_match`; anchor it with `^` and `$` to match
+    /// the whole text. When `capture` is given only the matches of that
+    /// capture are filtered, the others are kept as they are.
+    ///
+    /// Unlike a `#match?` predicate, the query doesn't need to change.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use regex::Regex;
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor =
+    ///     Extractor::new(lang, query).with_text_filter(Regex::new("^test_")?, Some("id"));
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn test_one(){} fn two(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches.len(), 1);
+    /// assert_eq!(extracted.matches[0].text, "test_one");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_text_filter(mut self, regex: Regex, capture: Option<&str>) -> Extractor {
+        self.text_filter = Some((regex, capture.map(|name| name.to_string())));
+        self
+    }
+
+    /// Stop extracting from a file after `max_matches` matches, like `grep --max-count`
+    ///
+    /// The cap is per file: every file of a batch still gets up to
+    /// `max_matches` matches. The rest of the file isn't matched at all, so
+    /// previewing a huge file is cheap. Files with more matches are marked
+    /// `ExtractedFile::truncated`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_max_matches(2);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn a(){} fn b(){} fn c(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let texts: Vec<_> = extracted.matches.iter().map(|m| m.text.as_str()).collect();
+    /// assert_eq!(texts, vec!["a", "b"]);
+    /// assert!(extracted.truncated);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_max_matches(mut self, max_matches: usize) -> Extractor {
+        self.max_matches = Some(max_matches);
+        self
+    }
+
+    /// Cap the matches a query cursor keeps in progress at once, see `QueryCursor::set_match_limit`
+    ///
+    /// tree_sitter tracks every match that could still complete while it
+    /// walks the tree, which can take a lot of memory on pathological files.
+    /// Once `match_limit` is reached, the oldest matches in progress are
+    /// dropped, so the results may be incomplete: matches that were found
+    /// are still yielded by `matches_iter`, but some never will be. Unlike
+    /// `with_max_matches` this doesn't bound how many matches are returned.
+    ///
+    /// The cursors built by `query_cursors` carry the limit, and tell whether
+    /// it was hit with `QueryCursor::did_exceed_match_limit` once they are
+    /// done (see `extract_with_cursors`). Files extracted while it was hit are
+    /// marked `ExtractedFile::truncated`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_match_limit(64);
+    /// let mut cursors = extractor.query_cursors();
+    /// let extracted = extractor
+    ///     .extract_with_cursors(None, b"fn a(){} fn b(){}", &mut Parser::new(), &mut cursors)?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches.len(), 2);
+    /// assert!(!extracted.truncated);
+    /// assert!(!cursors[0].did_exceed_match_limit());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_match_limit(mut self, match_limit: u32) -> Extractor {
+        self.match_limit = Some(match_limit);
+        self
+    }
+
+    /// Use the text of the `name` capture as the label of every capture of the same query match
+    ///
+    /// Declaration-level queries capture whole nodes, whose text is noisy to
+    /// show. With a label capture such as `@name`, the extracted matches of
+    /// a query match get its text as `ExtractedMatch::label`, which `Display`
+    /// and `ExtractedFile::outline` show instead of the whole text. Matches
+    /// of query matches without the label capture have no label. The label
+    /// capture itself must be kept, so don't start its name with `_`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item name: (identifier) @name) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query).with_label_capture("name");
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main() {\n    run();\n}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].label.as_deref(), Some("main"));
+    /// assert_eq!(extracted.to_string(), "NO FILE:1:1:function:main\nNO FILE:1:4:name:main\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_label_capture(mut self, name: &str) -> Extractor {
+        self.label_capture = Some(name.to_string());
+        self
+    }
+
+    /// Choose whether only the first kept capture of each query match is extracted
+    ///
+    /// Meant for queries built around one primary node plus helper captures
+    /// for annotations: the helpers still constrain the match but aren't
+    /// extracted. Captures left out by `with_ignore_underscore_captures` or
+    /// `with_capture_filter` don't count as first; the text filter of
+    /// `with_text_filter` applies afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item name: (identifier) @name parameters: (_) @params) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query).with_first_capture_only(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn a(){} fn b(x: u8){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let names: Vec<_> = extracted.matches.iter().map(|m| m.name.as_ref()).collect();
+    /// assert_eq!(names, vec!["function", "function"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_first_capture_only(mut self, first_capture_only: bool) -> Extractor {
+        self.first_capture_only = first_capture_only;
+        self
+    }
+
+    /// Choose whether the paths of extracted files are normalized, so outputs are the same on every OS
+    ///
+    /// Separators become `/` (Windows paths otherwise show `\`) and `.`
+    /// components are dropped, so `.\src\main.rs` on Windows is stored as
+    /// `src/main.rs`.
+    /// `ExtractedFile::file` holds the normalized path, so `Display` and
+    /// serialization both show it. Paths that aren't valid UTF-8 only have
+    /// their `.` components dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_normalized_paths(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(Some(Path::new("./src/./main.rs")), b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.to_string(), "src/main.rs:1:4:id:main\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_normalized_paths(mut self, normalize_paths: bool) -> Extractor {
+        self.normalize_paths = normalize_paths;
+        self
+    }
+
+    /// Store the paths of extracted files relative to `base_dir`, so results don't depend on where the sources are
+    ///
+    /// Handy to cache results or share them between machines with different
+    /// checkout locations. Paths outside of `base_dir` are kept as they are.
+    /// The prefix is compared component by component without touching the
+    /// filesystem, so give `base_dir` in the same form as the paths (both
+    /// absolute, say). Combined with `with_normalized_paths`, the prefix is
+    /// stripped first.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::{Path,PathBuf};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_base_dir(PathBuf::from("/home/me/project"));
+    /// let extracted = extractor
+    ///     .extract_from_text(Some(Path::new("/home/me/project/src/main.rs")), b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.file, Some(PathBuf::from("src/main.rs")));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_base_dir(mut self, base_dir: PathBuf) -> Extractor {
+        self.base_dir = Some(base_dir);
+        self
+    }
+
+    /// Choose whether `extract_from_file` memory-maps source files instead of reading them
+    ///
+    /// Mapping avoids copying the whole file into memory first, which lowers
+    /// the peak memory on multi-megabyte (often generated) files. Only
+    /// available with the `mmap` feature.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item) @function").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_mmap(true);
+    /// let extracted = extractor.extract_from_file(Path::new("data/error.rs"), &mut Parser::new())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn with_mmap(mut self, mmap: bool) -> Extractor {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Choose how sources are decoded before parsing, see `SourceEncoding`
+    ///
+    /// Applies to every method taking a source, from `parse` to
+    /// `extract_from_text`, `count_matches` or `files_with_matches`.
+    /// Positions are reported in the decoded UTF-8 text. Only available with
+    /// the `encoding` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor,SourceEncoding};
+    /// use tree_sitter::Parser;
+    ///
+    /// // "fn main(){}" in UTF-16LE, with a BOM
+    /// let mut source = vec![0xff, 0xfe];
+    /// source.extend("fn main(){}".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_encoding(SourceEncoding::Detect);
+    /// let extracted = extractor.extract_from_text(None, &source, &mut Parser::new())?.unwrap();
+    /// assert_eq!(extracted.matches[0].text, "main");
+    /// assert_eq!(extracted.matches[0].start_byte, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "encoding")]
+    pub fn with_encoding(mut self, encoding: SourceEncoding) -> Extractor {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Get the language of Extractor
+    ///
+    /// # Panics
+    ///
+    /// If the Extractor was built `from_raw`, which has no `Language`; see
+    /// `known_language` for those.
+    pub fn language(&self) -> &Language {
+        match &self.language {
+            Some(language) => language,
+            None => panic!(
+                "the extractor of {} was built from a raw grammar and has no language, see `known_language`",
+                self.file_type
+            ),
+        }
+    }
+
+    /// Get the language of Extractor, `None` if it was built `from_raw`
+    pub fn known_language(&self) -> Option<&Language> {
+        self.language.as_ref()
+    }
+
+    /// Get the names of the captures defined by the queries, one query after another
+    ///
+    /// A name shared by several queries is listed once per query. Captures
+    /// ignored because they start with `_` or are filtered out are listed too.
+    pub fn capture_names(&self) -> &[String] {
+        &self.captures
+    }
+
+    /// Whether any of the queries defines the capture `name`
+    ///
+    /// Handy to check up front that a user-provided query has the captures a
+    /// tool relies on, instead of silently getting no match.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::{Language,Extractor};
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    ///
+    /// assert_eq!(extractor.capture_names(), ["id", "function"]);
+    /// assert!(extractor.has_capture("id"));
+    /// assert!(!extractor.has_capture("name"));
+    /// ```
+    pub fn has_capture(&self, name: &str) -> bool {
+        self.captures.iter().any(|capture| capture == name)
+    }
+
+    /// Describe what the queries look for, without running them
+    ///
+    /// Handy to check that the right query was loaded, and which of its
+    /// captures are left out of the matches by the `_` convention (see
+    /// `with_ignore_underscore_captures`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::{Language,Extractor};
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query(r#"((function_item (identifier) @_name) @function (#eq? @_name "main")) (struct_item) @struct"#)
+    ///     .unwrap();
+    /// let info = Extractor::new(lang, query).explain();
+    ///
+    /// assert_eq!(info.pattern_count, 2);
+    /// assert_eq!(info.captures, ["_name", "function", "struct"]);
+    /// assert_eq!(info.ignored, ["_name"]);
+    /// ```
+    pub fn explain(&self) -> QueryInfo {
+        QueryInfo {
+            pattern_count: self.queries.iter().map(|query| query.pattern_count()).sum(),
+            captures: self.captures.clone(),
+            ignored: self
+                .captures
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| self.ignores.contains(index))
+                .map(|(_, name)| name.clone())
+                .collect(),
+        }
+    }
+
+    /// Extracted query information from one source file
+    pub fn extract_from_file(
+        &self,
+        path: &Path,
+        parser: &mut Parser,
+    ) -> Result<Option<ExtractedFile>> {
+        #[cfg(feature = "mmap")]
+        if self.mmap {
+            return self.extract_from_mapped_file(path, parser);
+        }
+
+        let source = fs::read(&path).context("could not read file")?;
+
+        self.extract_from_text(Some(path), &source, parser)
+    }
+
+    /// Extracted query information from one memory-mapped source file
+    #[cfg(feature = "mmap")]
+    fn extract_from_mapped_file(
+        &self,
+        path: &Path,
+        parser: &mut Parser,
+    ) -> Result<Option<ExtractedFile>> {
+        let file = fs::File::open(path).context("could not open file")?;
+        // mapping an empty file fails on some platforms, and there's no copy
+        // to save anyway
+        if file.metadata().context("could not read file")?.len() == 0 {
+            return self.extract_from_text(Some(path), &[], parser);
+        }
+
+        // safety: the mapping is only read while extracting and all the
+        // captured text is copied out of it. Like every mmap, a file truncated
+        // by another process at the same time can still make this crash.
+        let source = unsafe { Mmap::map(&file) }.context("could not map file")?;
+
+        self.extract_from_text(Some(path), &source, parser)
+    }
+
+    /// Extracted query information from a reader such as stdin
+    ///
+    /// The whole input is read before extracting. `label` is used as the file
+    /// name of the returned `ExtractedFile` (`-` is the usual choice for
+    /// stdin); without it the file name is shown as `NO FILE`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let input: &[u8] = b"fn main(){}";
+    /// let extracted = extractor
+    ///     .extract_from_reader(Some("-"), input, &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.to_string(), "-:1:4:id:main\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_reader<R: Read>(
+        &self,
+        label: Option<&str>,
+        mut reader: R,
+        parser: &mut Parser,
+    ) -> Result<Option<ExtractedFile>> {
+        let mut source = Vec::new();
+        reader
+            .read_to_end(&mut source)
+            .context("could not read input")?;
+
+        self.extract_from_text(label.map(Path::new), &source, parser)
+    }
+
+    /// Extracted query information from many source files, collecting errors per file
+    ///
+    /// A file that can't be read, parsed or whose captured text isn't valid
+    /// UTF-8 is reported alongside its path instead of failing the whole batch.
+    /// Files without any match are left out.
+    ///
+    /// # Returns
+    ///
+    /// * The successful extractions and the `(path, error)` pairs of failed files
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::PathBuf;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let paths = vec![PathBuf::from("data/error.rs"), PathBuf::from("missing.rs")];
+    /// let (extracted, errors) = extractor.extract_from_paths(&paths, &mut Parser::new());
+    /// for (path, err) in errors {
+    ///     eprintln!("{}: {:?}", path.display(), err);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_paths(
+        &self,
+        paths: &[PathBuf],
+        parser: &mut Parser,
+    ) -> (Vec<ExtractedFile>, Vec<(PathBuf, Error)>) {
+        self.extract_from_paths_with_progress(paths, parser, |_, _, _| ())
+    }
+
+    /// Extracted query information from many source files, reporting progress
+    ///
+    /// Same as `extract_from_paths`, but `progress` is called after each file
+    /// is processed, successfully or not, so long runs can drive a progress
+    /// bar or log.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - the source files
+    ///
+    /// * `parser` - tree_sitter Parser
+    ///
+    /// * `progress` - called with the processed file, the number of files
+    ///   processed so far (including it) and the total number of files
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::PathBuf;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let paths = vec![PathBuf::from("data/error.rs")];
+    /// let (extracted, errors) =
+    ///     extractor.extract_from_paths_with_progress(&paths, &mut Parser::new(), |path, done, total| {
+    ///         eprintln!("[{}/{}] {}", done, total, path.display())
+    ///     });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_paths_with_progress<F>(
+        &self,
+        paths: &[PathBuf],
+        parser: &mut Parser,
+        mut progress: F,
+    ) -> (Vec<ExtractedFile>, Vec<(PathBuf, Error)>)
+    where
+        F: FnMut(&Path, usize, usize),
+    {
+        let mut extracted = Vec::new();
+        let mut errors = Vec::new();
+
+        for (i, path) in paths.iter().enumerate() {
+            match self.extract_from_file(path, parser) {
+                Ok(Some(file)) => extracted.push(file),
+                Ok(None) => (),
+                Err(err) => errors.push((path.to_owned(), err)),
+            }
+            progress(path, i + 1, paths.len());
+        }
+
+        (extracted, errors)
+    }
+
+    /// Extracted query information from every source file under a directory
+    ///
+    /// Walks `root` recursively with the default `WalkOptions`, that is hidden
+    /// files are skipped, gitignore is honored and the results come sorted by
+    /// path, the same on every platform. See `extract_from_dir_with`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// for result in extractor.extract_from_dir(Path::new("data"), &mut Parser::new())? {
+    ///     match result {
+    ///         Ok(extracted) => print!("{}", extracted),
+    ///         Err(err) => eprintln!("{:?}", err),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_dir(
+        &self,
+        root: &Path,
+        parser: &mut Parser,
+    ) -> Result<Vec<Result<ExtractedFile>>> {
+        self.extract_from_dir_with(root, &WalkOptions::default(), parser)
+    }
+
+    /// Extracted query information from every source file under a directory
+    ///
+    /// Walks `root` recursively and extracts from the files whose extension
+    /// belongs to the language of the Extractor. A file that can't be read or
+    /// parsed doesn't abort the walk; its error is collected in place of its
+    /// result. Files without any match are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - the directory (or single file) to walk
+    ///
+    /// * `walk` - which files of the directory tree are visited
+    ///
+    /// * `parser` - tree_sitter Parser
+    ///
+    /// # Returns
+    ///
+    /// * One `ExtractedFile` or error per visited source file
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor,WalkOptions};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let walk = WalkOptions {
+    ///     respect_gitignore: false,
+    ///     ..WalkOptions::default()
+    /// };
+    /// let results = extractor.extract_from_dir_with(Path::new("."), &walk, &mut Parser::new())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_dir_with(
+        &self,
+        root: &Path,
+        walk: &WalkOptions,
+        parser: &mut Parser,
+    ) -> Result<Vec<Result<ExtractedFile>>> {
+        self.extract_from_dir_with_progress(root, walk, parser, |_, _, _| ())
+    }
+
+    /// Extracted query information from every source file under a directory, reporting progress
+    ///
+    /// Same as `extract_from_dir_with`, but the source files are collected
+    /// first so `progress` can be called with the total after each of them
+    /// is processed. See `extract_from_paths_with_progress`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor,WalkOptions};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let results = extractor.extract_from_dir_with_progress(
+    ///     Path::new("."),
+    ///     &WalkOptions::default(),
+    ///     &mut Parser::new(),
+    ///     |path, done, total| eprintln!("[{}/{}] {}", done, total, path.display()),
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_dir_with_progress<F>(
+        &self,
+        root: &Path,
+        walk: &WalkOptions,
+        parser: &mut Parser,
+        mut progress: F,
+    ) -> Result<Vec<Result<ExtractedFile>>>
+    where
+        F: FnMut(&Path, usize, usize),
+    {
+        fs::metadata(root).with_context(|| format!("couldn't read {}", root.display()))?;
+        let matcher = self.types_matcher()?;
+
+        let mut out = Vec::new();
+        let mut paths = Vec::new();
+        for entry in walk.walker(root) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    out.push(Err(err).context("couldn't walk the directory tree"));
+                    continue;
+                }
+            };
+
+            let is_file = entry.file_type().map(|ft| ft.is_file()).unwrap_or(false);
+            if is_file && matcher.matched(entry.path(), false).is_whitelist() {
+                paths.push(entry.into_path());
+            }
+        }
+
+        for (i, path) in paths.iter().enumerate() {
+            match self
+                .extract_from_file(path, parser)
+                .with_context(|| format!("could not extract matches from {}", path.display()))
+            {
+                Ok(Some(extracted)) => out.push(Ok(extracted)),
+                Ok(None) => (),
+                Err(err) => out.push(Err(err)),
+            }
+            progress(path, i + 1, paths.len());
+        }
+
+        Ok(out)
+    }
+
+    /// Extracted query information from many source files in parallel
+    ///
+    /// Files are spread across rayon's thread pool and every worker owns its
+    /// own `Parser`, since a `Parser` can't be shared. The results keep the
+    /// order of `paths`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::PathBuf;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let paths = vec![PathBuf::from("data/error.rs")];
+    /// for result in extractor.extract_from_paths_parallel(&paths) {
+    ///     if let Some(extracted) = result? {
+    ///         print!("{}", extracted);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_paths_parallel(
+        &self,
+        paths: &[PathBuf],
+    ) -> Vec<Result<Option<ExtractedFile>>> {
+        paths
+            .par_iter()
+            .map_init(Parser::new, |parser, path| {
+                self.extract_from_file(path, parser)
+                    .with_context(|| format!("could not extract matches from {}", path.display()))
+            })
+            .collect()
+    }
+
+    /// Extracted query information from one fragment program with a pooled parser
+    ///
+    /// Same as `extract_from_text`, but the `Parser` is taken from a
+    /// thread-local `ParserPool`, so it is configured once per thread and
+    /// language instead of once per call.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor.extract_pooled(None, b"fn main(){}")?.unwrap();
+    /// assert_eq!(extracted.matches.len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_pooled(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+    ) -> Result<Option<ExtractedFile>> {
+        thread_local! {
+            static POOL: RefCell<ParserPool> = RefCell::new(ParserPool::new());
+        }
+
+        let language = match self.language {
+            Some(language) => language,
+            // the pool only knows the bundled languages
+            None => return self.extract_from_text(path, source, &mut Parser::new()),
+        };
+
+        POOL.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            let parser = pool.get(language)?;
+            self.extract_from_text(path, source, parser)
+        })
+    }
+
+    /// Build a filetype matcher for the language of Extractor
+    fn types_matcher(&self) -> Result<Types> {
+        let language = match self.language {
+            Some(language) => language,
+            None => bail!(
+                "the files of {} are unknown, extract them one by one instead",
+                self.file_type
+            ),
+        };
+
+        let mut types_builder = language::types_builder()?;
+        types_builder.select(language.name_for_types_builder());
+        types_builder
+            .build()
+            .context("could not build a filetype matcher for the extractor")
+    }
+
+    /// Extracted query information from one fragment program
+    ///
+    /// A leading UTF-8 BOM is skipped: positions and byte offsets are those
+    /// of the source without it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Option: the path of source file
+    ///
+    /// * `source` - fragment program
+    ///
+    /// * `parser` - tree_sitter Parser
+    ///
+    /// # Returns
+    ///
+    /// * `ExtractedFile` object
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    ///         let extracted = extractor
+    ///        .extract_from_text(None, b"fn main(){println!(\"hello rust_hero\");}", &mut Parser::new())
+    ///        // From Result<Option<ExtractedFile>>
+    ///        .unwrap()
+    ///        // From Option<ExtractedFile>
+    ///        .unwrap();
+    ///
+    /// println!("{:?},{:?}，{:?}",extracted.matches.len(),extracted.matches[0].name,extracted.matches[0].text);
+    /// assert_eq!(extracted.matches.len(), 2);
+    /// assert_eq!(extracted.matches[0].name, "function");
+    /// assert_eq!(extracted.matches[0].text, "fn main(){println!(\"hello rust_hero\");}");
+    /// assert_eq!((extracted.matches[1].start_byte, extracted.matches[1].end_byte), (3, 7));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Query predicates filter the matches, for example only the functions whose
+    /// name starts with `test_`:
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query(r#"((identifier) @id (#match? @id "^test_"))
+    ///                     ((identifier) @other (#any-of? @other "a" "b"))"#)
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn test_one(){} fn two(){} fn b(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let texts: Vec<_> = extracted.matches.iter().map(|m| m.text.as_str()).collect();
+    /// assert_eq!(texts, vec!["test_one", "b"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_text(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+    ) -> Result<Option<ExtractedFile>> {
+        let extracted = self.extract_all(path, source, parser)?;
+
+        Ok(Some(extracted).filter(|extracted| !extracted.matches.is_empty()))
+    }
+
+    /// Extracted query information from one fragment program, even without any match
+    ///
+    /// Same as `extract_from_text`, but a source without any match gives an
+    /// `ExtractedFile` with no `matches` instead of `None`, so scanned files
+    /// can be told apart from skipped ones when processing many of them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(struct_item) @struct").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor.extract_all(None, b"fn main(){}", &mut Parser::new())?;
+    ///
+    /// assert!(extracted.matches.is_empty());
+    /// assert_eq!(extracted.file_type, "rust");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_all(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+    ) -> Result<ExtractedFile> {
+        self.extract_all_with_cursors(path, source, parser, &mut self.query_cursors())
+    }
+
+    /// Extracted query information from one fragment program with reusable `QueryCursor`s
+    ///
+    /// Same as `extract_from_text`, but the cursors are given instead of being
+    /// allocated on every call, like the `Parser` is. Batch callers going
+    /// through many small files can build them once with `query_cursors` and
+    /// keep reusing them. Settings such as a point range stay on the cursors
+    /// from one call to the next.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the source file
+    ///
+    /// * `source` - fragment program
+    ///
+    /// * `parser` - tree_sitter Parser
+    ///
+    /// * `cursors` - one `QueryCursor` per query, see `query_cursors`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let mut parser = Parser::new();
+    /// let mut cursors = extractor.query_cursors();
+    /// for source in [&b"fn a(){}"[..], b"fn b(){}", b"fn c(){}"] {
+    ///     let extracted = extractor
+    ///         .extract_with_cursors(None, source, &mut parser, &mut cursors)?
+    ///         .unwrap();
+    ///     assert_eq!(extracted.matches.len(), 1);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_with_cursors(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+        cursors: &mut [QueryCursor],
+    ) -> Result<Option<ExtractedFile>> {
+        let extracted = self.extract_all_with_cursors(path, source, parser, cursors)?;
+
+        Ok(Some(extracted).filter(|extracted| !extracted.matches.is_empty()))
+    }
+
+    /// Decode and parse a fragment program, then collect its matches even if there is none
+    fn extract_all_with_cursors(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+        cursors: &mut [QueryCursor],
+    ) -> Result<ExtractedFile> {
+        // zipping the queries with fewer cursors would silently skip queries
+        if cursors.len() != self.queries.len() {
+            bail!(
+                "got {} query cursors for {} queries, see `query_cursors`",
+                cursors.len(),
+                self.queries.len()
+            );
+        }
+
+        let source = self.prepare_source(source);
+
+        self.extract_prepared(path, &source, parser, cursors)
+    }
+
+    /// Parse an already prepared fragment program, then collect its matches even if there is none
+    fn extract_prepared(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+        cursors: &mut [QueryCursor],
+    ) -> Result<ExtractedFile> {
+        let tree = self.parse_with(source, parser, None)?;
+
+        self.extract_all_tree(path, &tree, source, cursors)
+    }
+
+    /// Extracted query information from a range of one fragment program
+    ///
+    /// tree_sitter only reports the matches intersecting `range`, so an editor
+    /// can ask for the visible region or the selection without paying for the
+    /// whole file. The file is still parsed as a whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the source file
+    ///
+    /// * `source` - fragment program
+    ///
+    /// * `parser` - tree_sitter Parser
+    ///
+    /// * `range` - zero-based rows and columns, end excluded
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::{Parser,Point};
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text_in_range(
+    ///         None,
+    ///         b"fn a(){}\nfn b(){}\nfn c(){}",
+    ///         &mut Parser::new(),
+    ///         Point::new(1, 0)..Point::new(2, 0),
+    ///     )?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches.len(), 1);
+    /// assert_eq!(extracted.matches[0].text, "b");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_text_in_range(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+        range: Range<Point>,
+    ) -> Result<Option<ExtractedFile>> {
+        let source: &[u8] = &self.prepare_source(source);
+        let tree = self.parse_with(source, parser, None)?;
+
+        let mut cursors = self.query_cursors();
+        for cursor in &mut cursors {
+            cursor.set_point_range(range.clone());
+        }
+
+        self.extract_tree(path, &tree, source, &mut cursors)
+    }
+
+    /// Extracted query information from an edited fragment program, reparsing incrementally
+    ///
+    /// `edit` is applied to `old_tree`, the tree of the source before the edit,
+    /// and tree_sitter only reparses what the edit touched. The new tree is
+    /// returned with the matches so it can be reused for the next edit.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the source file
+    ///
+    /// * `source` - fragment program, after the edit
+    ///
+    /// * `parser` - tree_sitter Parser
+    ///
+    /// * `old_tree` - tree of the fragment program before the edit, as `parse`
+    ///   or `extract_incremental` gave it
+    ///
+    /// * `edit` - where and how the source was edited, in the positions the
+    ///   matches are reported in
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::{InputEdit,Parser,Point};
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let mut parser = Parser::new();
+    /// let mut tree = extractor.parse(b"fn main(){}", &mut parser)?;
+    ///
+    /// // rename `main` to `start`
+    /// let edit = InputEdit {
+    ///     start_byte: 3,
+    ///     old_end_byte: 7,
+    ///     new_end_byte: 8,
+    ///     start_position: Point::new(0, 3),
+    ///     old_end_position: Point::new(0, 7),
+    ///     new_end_position: Point::new(0, 8),
+    /// };
+    /// let (new_tree, extracted) =
+    ///     extractor.extract_incremental(None, b"fn start(){}", &mut parser, &mut tree, &edit)?;
+    /// assert_eq!(extracted.unwrap().matches[0].text, "start");
+    /// assert_eq!(new_tree.root_node().end_byte(), 12);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_incremental(
+        &self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+        old_tree: &mut Tree,
+        edit: &InputEdit,
+    ) -> Result<(Tree, Option<ExtractedFile>)> {
+        let source: &[u8] = &self.prepare_source(source);
+        old_tree.edit(edit);
+        let tree = self.parse_with(source, parser, Some(old_tree))?;
+
+        let extracted = self.extract_tree(path, &tree, source, &mut self.query_cursors())?;
+        Ok((tree, extracted))
+    }
+
+    /// Extracted query information from code embedded in a fragment program, with the Extractor of its language
+    ///
+    /// The query of this Extractor finds the embedded regions, the way
+    /// tree_sitter injection queries do: a `@injection.content` capture is the
+    /// region, and its language is either given by the pattern with
+    /// `(#set! injection.language "...")` or captured as text by a
+    /// `@injection.language` capture of the same match. Each region is then
+    /// given to the Extractor of its language in `injections`; regions of
+    /// other languages are skipped.
+    ///
+    /// # Returns
+    ///
+    /// * One `ExtractedFile` per region with matches, in the order the
+    ///   regions were found. Their `file_type` is the embedded language, but
+    ///   positions, byte offsets and source size are those of the host file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::collections::HashMap;
+    /// use tree_sitter::Parser;
+    ///
+    /// let rust = Language::Rust;
+    /// let host = Extractor::new(
+    ///     rust,
+    ///     rust.parse_query(r#"((string_content) @injection.content (#set! injection.language "python"))"#)?,
+    /// );
+    /// let python = Language::Python;
+    /// let injections = HashMap::from([(
+    ///     python,
+    ///     Extractor::new(python, python.parse_query("(assignment left: (identifier) @name)")?),
+    /// )]);
+    /// let extracted = host.extract_with_injections(
+    ///     None,
+    ///     b"fn main() {\n    let code = \"x = 1\";\n}",
+    ///     &mut Parser::new(),
+    ///     &injections,
+    /// )?;
+    ///
+    /// assert_eq!(extracted.len(), 1);
+    /// assert_eq!(extracted[0].file_type, "python");
+    /// let name = &extracted[0].matches[0];
+    /// assert_eq!(name.text, "x");
+    /// assert_eq!((name.start.row, name.start.column, name.start_byte), (1, 16, 28));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_with_injections<'a>(
+        &'a self,
+        path: Option<&Path>,
+        source: &[u8],
+        parser: &mut Parser,
+        injections: &'a HashMap<Language, Extractor>,
+    ) -> Result<Vec<ExtractedFile<'a>>> {
+        let source: &[u8] = &self.prepare_source(source);
+        let tree = self.parse_with(source, parser, None)?;
+        let mut cursors = self.query_cursors();
+
+        // the content and the language of a region can be captured in any
+        // order, so they are gathered per query match first
+        let mut regions: BTreeMap<(usize, usize), (Option<Node>, Option<Language>)> =
+            BTreeMap::new();
+        for captured in self.captured_nodes(&tree, source, &mut cursors) {
+            let region = regions
+                .entry((captured.query_index, captured.match_index))
+                .or_insert_with(|| {
+                    let language = self.queries[captured.query_index]
+                        .property_settings(captured.pattern_index)
+                        .iter()
+                        .find(|property| &*property.key == "injection.language")
+                        .and_then(|property| property.value.as_deref())
+                        .and_then(|name| name.parse().ok());
+                    (None, language)
+                });
+
+            match self.captures[captured.capture_index].as_str() {
+                "injection.content" => region.0 = Some(captured.node),
+                "injection.language" => {
+                    region.1 = captured
+                        .node
+                        .utf8_text(source)
+                        .ok()
+                        .and_then(|name| name.trim().parse().ok())
+                }
+                _ => (),
+            }
+        }
+
+        let mut out = Vec::new();
+        for (content, language) in regions.into_values() {
+            let (content, extractor) = match (content, language.and_then(|l| injections.get(&l))) {
+                (Some(content), Some(extractor)) => (content, extractor),
+                _ => continue,
+            };
+
+            // the region is a slice of the decoded host, so it's not decoded again
+            let region = &source[content.byte_range()];
+            let mut extracted =
+                extractor.extract_prepared(path, region, parser, &mut extractor.query_cursors())?;
+            if !extracted.matches.is_empty() {
+                for extraction in &mut extracted.matches {
+                    extraction.start_byte += content.start_byte();
+                    extraction.end_byte += content.start_byte();
+                    extraction.start = shift_point(extraction.start, content.start_position());
+                    extraction.end = shift_point(extraction.end, content.start_position());
+                }
+                extracted.source_lines = count_lines(source);
+                extracted.source_bytes = source.len();
+                out.push(extracted);
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Collect the matches of a parsed tree into an `ExtractedFile`, if any
+    fn extract_tree(
+        &self,
+        path: Option<&Path>,
+        tree: &Tree,
+        source: &[u8],
+        cursors: &mut [QueryCursor],
+    ) -> Result<Option<ExtractedFile>> {
+        let extracted = self.extract_all_tree(path, tree, source, cursors)?;
+
+        Ok(Some(extracted).filter(|extracted| !extracted.matches.is_empty()))
+    }
+
+    /// Collect the matches of a parsed tree into an `ExtractedFile`, even if there is none
+    fn extract_all_tree(
+        &self,
+        path: Option<&Path>,
+        tree: &Tree,
+        source: &[u8],
+        cursors: &mut [QueryCursor],
+    ) -> Result<ExtractedFile> {
+        let (mut extracted_matches, cut) = {
+            let mut matches = self.prepared_matches_iter(tree, source, cursors);
+            let extracted_matches = matches
+                .by_ref()
+                .take(self.max_matches.unwrap_or(usize::MAX))
+                .collect::<Result<Vec<ExtractedMatch>>>()?;
+
+            // only one more match is looked for, to know if there was any left
+            let cut = self.max_matches.is_some() && matches.next().is_some();
+            (extracted_matches, cut)
+        };
+        if let Some(label_capture) = &self.label_capture {
+            set_labels(&mut extracted_matches, label_capture);
+        }
+
+        Ok(ExtractedFile {
+            file: path.map(|p| self.output_path(p)),
+            file_type: self.file_type.clone(),
+            matches: extracted_matches,
+            source_lines: count_lines(source),
+            source_bytes: source.len(),
+            truncated: cut || cursors.iter().any(|cursor| cursor.did_exceed_match_limit()),
+        })
+    }
+
+    /// Matches of a fragment program borrowing their text from `source` instead of copying it
+    ///
+    /// Same matches as `extract_from_text`, but each `BorrowedMatch` holds a
+    /// slice of `source`, so no `String` is allocated per capture. Meant for
+    /// callers keeping the source around anyway, on big files with many
+    /// matches. A slice can't be decoded, so the source is taken as UTF-8
+    /// whatever `with_lossy_text` says, and invalid UTF-8 in a capture is an
+    /// error, as is a source that `with_encoding` has to decode. Labels
+    /// aren't set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let source = b"fn main(){} fn other(){}".to_vec();
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let matches = extractor.extract_borrowed(&source, &mut Parser::new())?;
+    ///
+    /// let texts: Vec<&str> = matches.iter().map(|m| m.text).collect();
+    /// assert_eq!(texts, vec!["main", "other"]);
+    /// assert_eq!(matches[1].start_byte, 15);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_borrowed<'query, 'src>(
+        &'query self,
+        source: &'src [u8],
+        parser: &mut Parser,
+    ) -> Result<Vec<BorrowedMatch<'query, 'src>>> {
+        let source = match self.prepare_source(source) {
+            Cow::Borrowed(source) => source,
+            Cow::Owned(_) => bail!("could not borrow the text of a decoded source"),
+        };
+        let tree = self.parse_with(source, parser, None)?;
+
+        let mut cursors = self.query_cursors();
+        let matches = self
+            .captured_nodes(&tree, source, &mut cursors)
+            .map(move |captured| {
+                let node = captured.node;
+                let (range, end) = self.capture_span(node, source);
+                Ok(BorrowedMatch {
+                    kind: node.kind(),
+                    name: &self.captures[captured.capture_index],
+                    text: std::str::from_utf8(&source[range.clone()])
+                        .context("could not extract text from capture")?,
+                    start: node.start_position(),
+                    end,
+                    start_byte: range.start,
+                    end_byte: range.end,
+                    query_index: captured.query_index,
+                    match_index: captured.match_index,
+                    pattern_index: captured.pattern_index,
+                })
+            })
+            .filter(|borrowed| match borrowed {
+                Ok(borrowed) => self.keeps_text(borrowed.name, borrowed.text),
+                // errors are never filtered out
+                Err(_) => true,
+            })
+            .take(self.max_matches.unwrap_or(usize::MAX))
+            .collect();
+
+        matches
+    }
+
+    /// Rewrite the matches of a fragment program, as the matching half of a codemod
+    ///
+    /// `replace` is called with every match and returns the text to put in
+    /// place of it, or `None` to leave it alone. Replacements are applied
+    /// back to front by byte offset so the offsets stay valid. Two replaced
+    /// matches that overlap (a node and its child for example) are an error,
+    /// since there is no telling which one should win.
+    /// The rewritten source is the program as it was parsed, so a BOM is
+    /// left out, and a source decoded by `with_encoding` comes back as UTF-8.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let rewritten = extractor.rewrite(b"fn old_a(){} fn b(){}", &mut Parser::new(), |m| {
+    ///     m.text.strip_prefix("old_").map(|rest| format!("new_{}", rest))
+    /// })?;
+    /// assert_eq!(rewritten, "fn new_a(){} fn b(){}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rewrite<F>(&self, source: &[u8], parser: &mut Parser, replace: F) -> Result<String>
+    where
+        F: Fn(&ExtractedMatch) -> Option<String>,
+    {
+        let source: &[u8] = &self.prepare_source(source);
+        let tree = self.parse_with(source, parser, None)?;
+
+        let mut cursors = self.query_cursors();
+        let mut replacements = Vec::new();
+        for extracted in self
+            .prepared_matches_iter(&tree, source, &mut cursors)
+            .take(self.max_matches.unwrap_or(usize::MAX))
+        {
+            let extracted = extracted?;
+            if let Some(replacement) = replace(&extracted) {
+                replacements.push((extracted.start_byte..extracted.end_byte, replacement));
+            }
+        }
+
+        replacements.sort_by_key(|(range, _)| (range.start, range.end));
+        for pair in replacements.windows(2) {
+            let (first, second) = (&pair[0].0, &pair[1].0);
+            if second.start < first.end {
+                bail!(
+                    "could not rewrite overlapping matches at bytes {}..{} and {}..{}",
+                    first.start,
+                    first.end,
+                    second.start,
+                    second.end
+                );
+            }
+        }
+
+        let mut rewritten = source.to_vec();
+        for (range, replacement) in replacements.into_iter().rev() {
+            rewritten.splice(range, replacement.into_bytes());
+        }
+
+        String::from_utf8(rewritten).context("the rewritten source is not valid UTF-8")
+    }
+
+    /// Parse a fragment program with the language of Extractor
+    ///
+    /// The whole syntax tree is returned, for running other `QueryCursor`
+    /// passes or walking the nodes by hand. The source is decoded and its
+    /// BOM skipped as for `extract_from_text`, so the tree is that of the
+    /// program the matches are reported in.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item) @function").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let tree = extractor.parse(b"fn main(){}", &mut Parser::new())?;
+    /// assert_eq!(tree.root_node().kind(), "source_file");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse(&self, source: &[u8], parser: &mut Parser) -> Result<Tree> {
+        self.parse_with(&self.prepare_source(source), parser, None)
+    }
+
+    /// Parse a fragment program, reusing the unchanged parts of `old_tree` if given
+    fn parse_with(
+        &self,
+        source: &[u8],
+        parser: &mut Parser,
+        old_tree: Option<&Tree>,
+    ) -> Result<Tree> {
+        // a parser reused across calls (see `ParserPool`) is usually already
+        // configured for our language, so skip the redundant work
+        if parser.language() != Some(self.ts_language) {
+            parser
+                .set_language(self.ts_language)
+                .context("could not set language")?;
+        }
+
+        // the parser may be shared with other extractors, so always set our
+        // own timeout (zero means none)
+        let timeout_micros = self.timeout.map_or(0, |timeout| timeout.as_micros() as u64);
+        parser.set_timeout_micros(timeout_micros);
+
+        match parser.parse(source, old_tree) {
+            Some(tree) => Ok(tree),
+            None => match self.timeout {
+                Some(timeout) => {
+                    // otherwise the next parse would resume this one
+                    parser.reset();
+                    Err(ParseTimeout { timeout }.into())
+                }
+                // note: without a timeout (and we never set a cancellation flag)
+                // this can only be a language error. Buuuut we also always set
+                // the language above so if this happens we also know it's an
+                // internal error.
+                None => bail!(
+                    "could not parse to a tree. This is an internal error and should be reported."
+                ),
+            },
+        }
+    }
+
+    /// Build one fresh `QueryCursor` per query of the Extractor, as needed by `matches_iter`
+    ///
+    /// The cursors carry the limit set `with_match_limit`, if any.
+    pub fn query_cursors(&self) -> Vec<QueryCursor> {
+        self.queries
+            .iter()
+            .map(|_| {
+                let mut cursor = QueryCursor::new();
+                if let Some(match_limit) = self.match_limit {
+                    cursor.set_match_limit(match_limit);
+                }
+                cursor
+            })
+            .collect()
+    }
+
+    /// Lazily iterate over the matches of a parsed tree
+    ///
+    /// Nothing is collected: matches are built one at a time as the iterator
+    /// is advanced, so stopping early skips the rest of the work. A
+    /// `QueryCursor` can only run one query at a time, so `cursors` holds one
+    /// per query (see `query_cursors`).
+    ///
+    /// Matches come query after query, and query match after query match
+    /// within a query. The captures of a query match come in document order:
+    /// by start, then the longer span first, so a node comes before the
+    /// nodes it contains. A node and a descendant with the same span (an
+    /// expression statement and its expression, say) come outer before
+    /// inner, and several captures of one node come in the order of the
+    /// capture names in the query.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - the tree `parse` gave for `source`
+    ///
+    /// * `source` - fragment program, its BOM is skipped as by `parse`
+    ///
+    /// * `cursors` - one `QueryCursor` per query
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let source = b"fn helper(){} fn main(){} fn other(){}";
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let tree = extractor.parse(source, &mut Parser::new())?;
+    /// let mut cursors = extractor.query_cursors();
+    /// let main = extractor
+    ///     .matches_iter(&tree, source, &mut cursors)
+    ///     .find(|m| m.as_ref().map(|m| m.text == "main").unwrap_or(true))
+    ///     .unwrap()?;
+    /// assert_eq!(main.start.column, 17);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn matches_iter<'query, 'a>(
+        &'query self,
+        tree: &'a Tree,
+        source: &'a [u8],
+        cursors: &'a mut [QueryCursor],
+    ) -> impl Iterator<Item = Result<ExtractedMatch<'query>>> + 'a
+    where
+        'query: 'a,
+    {
+        self.prepared_matches_iter(tree, skip_bom(source), cursors)
+    }
+
+    /// Lazily iterate over the matches of a tree parsed from an already prepared source, see `matches_iter`
+    fn prepared_matches_iter<'query, 'a>(
+        &'query self,
+        tree: &'a Tree,
+        source: &'a [u8],
+        cursors: &'a mut [QueryCursor],
+    ) -> impl Iterator<Item = Result<ExtractedMatch<'query>>> + 'a
+    where
+        'query: 'a,
+    {
+        self.captured_nodes(tree, source, cursors)
+            // without text, the filter has to look at the source
+            .filter(move |captured| !self.skip_text || self.keeps_node_text(captured, source))
+            .map(move |captured| {
+                let node = captured.node;
+                let (range, end) = self.capture_span(node, source);
+                let text = if self.skip_text {
+                    String::new()
+                } else {
+                    self.capture_text(&source[range.clone()])?
+                };
+                Ok(ExtractedMatch {
+                    kind: Cow::Borrowed(node.kind()),
+                    name: Cow::Borrowed(&self.captures[captured.capture_index]),
+                    text,
+                    start: node.start_position(),
+                    end,
+                    start_byte: range.start,
+                    end_byte: range.end,
+                    query_index: captured.query_index,
+                    match_index: captured.match_index,
+                    pattern_index: captured.pattern_index,
+                    parent_kind: node.parent().map(|parent| Cow::Borrowed(parent.kind())),
+                    field_name: field_name(node).map(Cow::Borrowed),
+                    label: None,
+                    one_based: self.one_based,
+                    max_text_len: self.max_text_len,
+                    single_line_text: self.single_line_text,
+                    escaped_text: self.escaped_text,
+                    end_position: self.end_position,
+                })
+            })
+            .filter(move |extracted| match extracted {
+                Ok(extracted) => {
+                    self.skip_text || self.keeps_text(&extracted.name, &extracted.text)
+                }
+                // errors are never filtered out
+                Err(_) => true,
+            })
+    }
+
+    /// Lazily iterate over the kept captured nodes of a parsed tree
+    fn captured_nodes<'a>(
+        &'a self,
+        tree: &'a Tree,
+        source: &'a [u8],
+        cursors: &'a mut [QueryCursor],
+    ) -> impl Iterator<Item = CapturedNode<'a>> + 'a {
+        let root = tree.root_node();
+
+        self.queries
+            .iter()
+            .map(|query| query.as_ref())
+            .zip(cursors.iter_mut())
+            .enumerate()
+            .flat_map(move |(query_index, (query, cursor))| {
+                let offset = self.capture_offsets[query_index];
+
+                // tree_sitter already filters on the text predicates (`#eq?`,
+                // `#not-eq?`, `#match?` and `#not-match?`) while matching, but the
+                // others are left to us.
+                cursor
+                    .matches(query, root, source)
+                    .filter(move |query_match| {
+                        satisfies_general_predicates(query, query_match, source)
+                    })
+                    .enumerate()
+                    .flat_map(move |(match_index, query_match)| {
+                        // the order of captures within a match is tree_sitter's
+                        // business, so it is pinned here, see `matches_iter`
+                        let mut captures = query_match.captures.to_vec();
+                        captures.sort_by(|a, b| {
+                            let span = |node: Node| (node.start_byte(), Reverse(node.end_byte()));
+                            span(a.node)
+                                .cmp(&span(b.node))
+                                // depth is costly, only ask for it on ties
+                                .then_with(|| {
+                                    if a.node == b.node {
+                                        Ordering::Equal
+                                    } else {
+                                        node_depth(a.node).cmp(&node_depth(b.node))
+                                    }
+                                })
+                                .then_with(|| a.index.cmp(&b.index))
+                        });
+
+                        captures
+                            .into_iter()
+                            .map(move |capture| CapturedNode {
+                                query_index,
+                                match_index,
+                                pattern_index: query_match.pattern_index,
+                                // note: the casts here could potentially break if run on a 16-bit
+                                // microcontroller. I don't think this is a huge problem, though,
+                                // since even the gnarliest queries I've written have something on
+                                // the order of 20 matches. Nowhere close to 2^16!
+                                capture_index: offset + capture.index as usize,
+                                node: capture.node,
+                            })
+                            .filter(move |captured| self.keeps_capture(captured.capture_index))
+                            .take(if self.first_capture_only {
+                                1
+                            } else {
+                                usize::MAX
+                            })
+                    })
+            })
+    }
+
+    /// Count the matches of a fragment program without building them
+    ///
+    /// Gives the length `extract_from_text` would give to `matches`, but no
+    /// `ExtractedMatch` is built and no text is copied, which is much cheaper
+    /// for "how many" checks over a big tree. Captures are filtered the same
+    /// way; the captured text is only looked at when there is a text filter.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @_id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let count = extractor.count_matches(b"fn a(){} fn b(){}", &mut Parser::new())?;
+    /// assert_eq!(count, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn count_matches(&self, source: &[u8], parser: &mut Parser) -> Result<usize> {
+        let source: &[u8] = &self.prepare_source(source);
+        let tree = self.parse_with(source, parser, None)?;
+
+        let mut cursors = self.query_cursors();
+        let count = self
+            .captured_nodes(&tree, source, &mut cursors)
+            .filter(|captured| self.keeps_node_text(captured, source))
+            .take(self.max_matches.unwrap_or(usize::MAX))
+            .count();
+
+        Ok(count)
+    }
+
+    /// Whether a fragment program has any match, stopping at the first one
+    ///
+    /// Same as `count_matches(..) > 0`, but the query stops running as soon
+    /// as a kept capture is found, which makes filtering many files cheap.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(attribute_item) @attribute").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let mut parser = Parser::new();
+    /// assert!(extractor.is_match(b"#[test]\nfn one(){}", &mut parser)?);
+    /// assert!(!extractor.is_match(b"fn two(){}", &mut parser)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn is_match(&self, source: &[u8], parser: &mut Parser) -> Result<bool> {
+        let source: &[u8] = &self.prepare_source(source);
+        let tree = self.parse_with(source, parser, None)?;
+
+        let mut cursors = self.query_cursors();
+        let found = self
+            .captured_nodes(&tree, source, &mut cursors)
+            .any(|captured| self.keeps_node_text(&captured, source));
+
+        Ok(found && self.max_matches != Some(0))
+    }
+
+    /// Paths of the files having any match, like `grep -l`
+    ///
+    /// Each file is only matched until its first match (see `is_match`), no
+    /// match is built. Files that can't be read or parsed are reported
+    /// alongside their path instead of failing the whole batch, as in
+    /// `extract_from_paths`.
+    ///
+    /// # Returns
+    ///
+    /// * The paths of the files with matches, in the order of `paths`, and
+    ///   the `(path, error)` pairs of failed files
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::PathBuf;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(unsafe_block) @unsafe").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let paths = vec![PathBuf::from("data/error.rs"), PathBuf::from("src/main.rs")];
+    /// let (matching, _errors) = extractor.files_with_matches(&paths, &mut Parser::new());
+    /// for path in matching {
+    ///     println!("{}", path.display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn files_with_matches(
+        &self,
+        paths: &[PathBuf],
+        parser: &mut Parser,
+    ) -> (Vec<PathBuf>, Vec<(PathBuf, Error)>) {
+        self.files_by_match(paths, parser, true)
+    }
+
+    /// Paths of the files without any match, like `grep -L`
+    ///
+    /// The complement of `files_with_matches`, for audits such as finding
+    /// the source files without tests. A file is known to have matches as
+    /// soon as its first match is found. Files that can't be read or parsed
+    /// are reported as errors, not as files without matches.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::PathBuf;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query(r#"((attribute_item (attribute (identifier) @_name)) @test (#eq? @_name "test"))"#)
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let paths = vec![PathBuf::from("data/error.rs"), PathBuf::from("src/main.rs")];
+    /// let (untested, _errors) = extractor.files_without_matches(&paths, &mut Parser::new());
+    /// for path in untested {
+    ///     println!("{}", path.display());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn files_without_matches(
+        &self,
+        paths: &[PathBuf],
+        parser: &mut Parser,
+    ) -> (Vec<PathBuf>, Vec<(PathBuf, Error)>) {
+        self.files_by_match(paths, parser, false)
+    }
+
+    /// Paths of the files having matches or not, following `has_matches`
+    fn files_by_match(
+        &self,
+        paths: &[PathBuf],
+        parser: &mut Parser,
+        has_matches: bool,
+    ) -> (Vec<PathBuf>, Vec<(PathBuf, Error)>) {
+        let mut kept = Vec::new();
+        let mut errors = Vec::new();
+
+        for path in paths {
+            match self.file_is_match(path, parser) {
+                Ok(is_match) if is_match == has_matches => kept.push(path.to_owned()),
+                Ok(_) => (),
+                Err(err) => errors.push((path.to_owned(), err)),
+            }
+        }
+
+        (kept, errors)
+    }
+
+    /// Whether a source file has any match, see `is_match`
+    fn file_is_match(&self, path: &Path, parser: &mut Parser) -> Result<bool> {
+        let source = fs::read(path).context("could not read file")?;
+
+        self.is_match(&source, parser)
+    }
+
+    /// S-expressions of the captured nodes of a fragment program, for debugging queries
+    ///
+    /// Shows the subtree tree_sitter matched for every capture, like
+    /// `tree-sitter parse` scoped to the match, to understand why a query
+    /// captures what it does. Captures are filtered as in `extract_from_text`,
+    /// except for the text filter and `max_matches`.
+    ///
+    /// # Returns
+    ///
+    /// * The capture name and the s-expression of each captured node
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let sexps = extractor.sexp_for_matches(b"fn main(){}", &mut Parser::new())?;
+    /// assert_eq!(sexps, vec![("id".to_string(), "(identifier)".to_string())]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sexp_for_matches(
+        &self,
+        source: &[u8],
+        parser: &mut Parser,
+    ) -> Result<Vec<(String, String)>> {
+        let source: &[u8] = &self.prepare_source(source);
+        let tree = self.parse_with(source, parser, None)?;
+
+        let mut cursors = self.query_cursors();
+        let sexps = self
+            .captured_nodes(&tree, source, &mut cursors)
+            .map(|captured| {
+                (
+                    self.captures[captured.capture_index].clone(),
+                    captured.node.to_sexp(),
+                )
+            })
+            .collect();
+
+        Ok(sexps)
+    }
+
+    /// Whether the capture at `capture_index` in `captures` ends up in the matches
+    fn keeps_capture(&self, capture_index: usize) -> bool {
+        if self.ignores.contains(&capture_index) {
+            return false;
+        }
+        match &self.capture_filter {
+            Some(kept) => kept.contains(&capture_index),
+            None => true,
+        }
+    }
+
+    /// Whether the `text` captured as `name` passes the text filter, if any
+    fn keeps_text(&self, name: &str, text: &str) -> bool {
+        match &self.text_filter {
+            Some((regex, Some(capture))) if capture == name => regex.is_match(text),
+            Some((_, Some(_))) => true,
+            Some((regex, None)) => regex.is_match(text),
+            None => true,
+        }
+    }
+
+    /// Whether the text of a captured node passes the text filter, if any, without copying it
+    fn keeps_node_text(&self, captured: &CapturedNode, source: &[u8]) -> bool {
+        if self.text_filter.is_none() {
+            return true;
+        }
+
+        let (range, _) = self.capture_span(captured.node, source);
+        let text = String::from_utf8_lossy(&source[range]);
+        self.keeps_text(&self.captures[captured.capture_index], &text)
+    }
+
+    /// Get the byte range and end point of a captured node, without the `\r` of a CRLF line end if asked to
+    fn capture_span(&self, node: Node, source: &[u8]) -> (Range<usize>, Point) {
+        let (range, end) = (node.byte_range(), node.end_position());
+
+        // tree_sitter only breaks lines on `\n`, so a capture running to the
+        // end of a CRLF line keeps the `\r` and still ends on its first row
+        if self.trim_cr
+            && node.start_position().row == end.row
+            && source[range.clone()].ends_with(b"\r")
+        {
+            return (
+                range.start..range.end - 1,
+                Point::new(end.row, end.column - 1),
+            );
+        }
+
+        (range, end)
+    }
+
+    /// Get the path stored in the extracted file of `path`, relative to the base directory and normalized if asked to
+    fn output_path(&self, path: &Path) -> PathBuf {
+        let path = match &self.base_dir {
+            Some(base_dir) => path.strip_prefix(base_dir).unwrap_or(path),
+            None => path,
+        };
+
+        if !self.normalize_paths {
+            return path.to_owned();
+        }
+
+        let path: PathBuf = path
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        match path.to_str() {
+            Some(raw) => PathBuf::from(raw.replace(MAIN_SEPARATOR, "/")),
+            None => path,
+        }
+    }
+
+    /// Get the program a source is parsed as: decoded if asked to, and without its BOM
+    ///
+    /// Every method taking a source goes through this, so that they all
+    /// report the same positions.
+    fn prepare_source<'source>(&self, source: &'source [u8]) -> Cow<'source, [u8]> {
+        #[cfg(feature = "encoding")]
+        let source = self.encoding.decode(source);
+        #[cfg(not(feature = "encoding"))]
+        let source = Cow::Borrowed(source);
+
+        // a BOM isn't part of the program, the grammar would choke on it and
+        // the columns of the first line would be off
+        match source {
+            Cow::Borrowed(source) => Cow::Borrowed(skip_bom(source)),
+            Cow::Owned(mut source) => {
+                if source.starts_with(UTF8_BOM) {
+                    source.drain(..UTF8_BOM.len());
+                }
+                Cow::Owned(source)
+            }
+        }
+    }
+
+    /// Get the text of captured bytes, decoding invalid UTF-8 lossily if asked to
+    fn capture_text(&self, bytes: &[u8]) -> Result<String> {
+        if self.lossy_text {
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        } else {
+            std::str::from_utf8(bytes)
+                .map(|unowned| unowned.to_string())
+                .context("could not extract text from capture")
+        }
+    }
+}
+
+/// A captured node of a query match, see `Extractor::captured_nodes`
+struct CapturedNode<'tree> {
+    /// Index of the query in `Extractor::queries`
+    query_index: usize,
+    /// Index of the query match among the matches of its query
+    match_index: usize,
+    /// Index of the pattern of the query which matched
+    pattern_index: usize,
+    /// Index of the capture in `Extractor::captures`
+    capture_index: usize,
+    /// The captured node
+    node: Node<'tree>,
+}
+
+/// Get the field name of `node` in its parent, like `name` for the identifier of a function
+fn field_name(node: Node) -> Option<&'static str> {
+    let parent = node.parent()?;
+    let mut cursor = parent.walk();
+    if !cursor.goto_first_child() {
+        return None;
+    }
+
+    loop {
+        if cursor.node() == node {
+            return cursor.field_name();
+        }
+        if !cursor.goto_next_sibling() {
+            return None;
+        }
+    }
+}
+
+/// Get the number of ancestors of `node`, to tell it from a descendant with the same span
+fn node_depth(node: Node) -> usize {
+    std::iter::successors(node.parent(), |parent| parent.parent()).count()
+}
+
+/// Set the label of every match to the text of the `label_capture` match of
+/// its query match, the first one if there are several
+fn set_labels(matches: &mut [ExtractedMatch], label_capture: &str) {
+    let mut labels: HashMap<(usize, usize), String> = HashMap::new();
+    for extraction in matches.iter().filter(|m| m.name == label_capture) {
+        labels
+            .entry((extraction.query_index, extraction.match_index))
+            .or_insert_with(|| extraction.text.clone());
+    }
+
+    for extraction in matches {
+        extraction.label = labels
+            .get(&(extraction.query_index, extraction.match_index))
+            .cloned();
+    }
+}
+
+/// Move a point of an embedded region to the coordinates of its host, `origin` being the start of the region
+fn shift_point(point: Point, origin: Point) -> Point {
+    if point.row == 0 {
+        Point::new(origin.row, origin.column + point.column)
+    } else {
+        Point::new(origin.row + point.row, point.column)
+    }
+}
+
+/// Cut `text` to `max_chars` chars, followed by `…` if it was longer
+fn truncate(text: &str, max_chars: usize) -> Cow<'_, str> {
+    // the byte offset of the first char left out is always a char boundary
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => Cow::Owned(format!("{}…", &text[..end])),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Count the lines of `source`, the last one counted even without a trailing newline
+fn count_lines(source: &[u8]) -> usize {
+    let newlines = source.iter().filter(|byte| **byte == b'\n').count();
+    match source.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+/// Byte order mark some editors put at the start of UTF-8 files
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Get `source` without its leading UTF-8 BOM, if any
+fn skip_bom(source: &[u8]) -> &[u8] {
+    source.strip_prefix(UTF8_BOM).unwrap_or(source)
+}
+
+/// Get the indices of the captures whose name starts with `_`
+fn underscore_captures(captures: &[String]) -> HashSet<usize> {
+    captures
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.starts_with('_'))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// What the queries of an Extractor look for, see `Extractor::explain`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryInfo {
+    /// Number of patterns, all queries together
+    pub pattern_count: usize,
+    /// Names of the captures, one query after another like `Extractor::capture_names`
+    pub captures: Vec<String>,
+    /// Names of the captures left out of the matches because they start with `_`
+    pub ignored: Vec<String>,
+}
+
+/// Error of a parse which took longer than the timeout of the Extractor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseTimeout {
+    /// The timeout which was exceeded
+    pub timeout: Duration,
+}
+
+impl Display for ParseTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "parsing took longer than {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for ParseTimeout {}
+
+/// Check the general predicates of the pattern of `query_match`
+///
+/// `#any-of?` and `#not-any-of?` are supported; other general predicates are
+/// left for the caller to interpret and don't filter anything out.
+fn satisfies_general_predicates(query: &Query, query_match: &QueryMatch, source: &[u8]) -> bool {
+    query
+        .general_predicates(query_match.pattern_index)
+        .iter()
+        .all(|predicate| match predicate.operator.as_ref() {
+            "any-of?" | "not-any-of?" => {
+                let is_positive = predicate.operator.as_ref() == "any-of?";
+                match predicate.args.split_first() {
+                    Some((QueryPredicateArg::Capture(index), values)) => {
+                        query_match.nodes_for_capture_index(*index).all(|node| {
+                            let text = &source[node.byte_range()];
+                            let found = values.iter().any(|value| match value {
+                                QueryPredicateArg::String(value) => value.as_bytes() == text,
+                                QueryPredicateArg::Capture(_) => false,
+                            });
+                            found == is_positive
+                        })
+                    }
+                    _ => true,
+                }
+            }
+            _ => true,
+        })
+}
+
+/// Extracted query from source file
+///
+/// It can be deserialized back from its serialized form, whether the
+/// coordinates of the matches were serialized one-based or zero-based.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{ExtractedFile,Language,Extractor};
+/// use tree_sitter::Parser;
+///
+/// let lang = Language::Rust;
+/// let query = lang
+///     .parse_query("(function_item (identifier) @id) @function")
+///     .unwrap();
+/// let extractor = Extractor::new(lang, query);
+/// let extracted = extractor
+///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+///     .unwrap();
+///
+/// let json = serde_json::to_string(&extracted)?;
+/// let cached: ExtractedFile<'static> = serde_json::from_str(&json)?;
+/// assert_eq!(cached, extracted);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExtractedFile<'query> {
+    /// Extracted source file
+    pub file: Option<PathBuf>,
+    /// Language
+    pub file_type: String,
+    /// A set of patterns that match nodes in a syntax tree, in the order of `Extractor::matches_iter`
+    pub matches: Vec<ExtractedMatch<'query>>,
+    /// Number of lines of the source, the last one counted even without a trailing newline
+    #[serde(default)]
+    pub source_lines: usize,
+    /// Size of the source in bytes
+    #[serde(default)]
+    pub source_bytes: usize,
+    /// Whether `matches` is incomplete, because `Extractor::with_max_matches`
+    /// cut it or the limit of `Extractor::with_match_limit` was hit
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl<'query> ExtractedFile<'query> {
+    /// Get the file name shown in outputs
+    pub(crate) fn filename(&self) -> &str {
+        // TODO: is there a better way to do this unwrapping? This implementation
+        // turns non-UTF-8 paths into "NON-UTF8 FILENAME". I don't know exactly
+        // what circumstances that could happen in... maybe we should just wait
+        // for bug reports?
+        self.file
+            .as_ref()
+            .map(|f| f.to_str().unwrap_or("NON-UTF8 FILENAME"))
+            .unwrap_or("NO FILE")
+    }
+
+    /// Sort the matches by their start position in the source
+    ///
+    /// Matches come out in query pattern order; this puts them in document
+    /// order instead. The sort is stable, so matches starting at the same
+    /// position keep their relative order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let functions = lang.parse_query("(function_item) @item").unwrap();
+    /// let structs = lang.parse_query("(struct_item) @item").unwrap();
+    /// let extractor = Extractor::from_queries(lang, vec![functions, structs]);
+    /// let mut extracted = extractor
+    ///     .extract_from_text(None, b"struct S;\nfn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// extracted.sort_by_position();
+    /// assert_eq!(extracted.matches[0].kind(), "struct_item");
+    /// assert_eq!(extracted.matches[1].kind(), "function_item");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sort_by_position(&mut self) {
+        self.matches
+            .sort_by_key(|extraction| (extraction.start.row, extraction.start.column));
+    }
+
+    /// Remove the matches which repeat an earlier match, keeping the first one
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - what makes two matches the same
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{DedupKey,Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item name: (identifier) @name) (identifier) @id")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let mut extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// extracted.dedup(DedupKey::SpanAndName);
+    /// assert_eq!(extracted.matches.len(), 2);
+    /// extracted.dedup(DedupKey::Span);
+    /// assert_eq!(extracted.matches.len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dedup(&mut self, key: DedupKey) {
+        let mut seen = HashSet::new();
+        self.matches.retain(|extraction| {
+            let name = match key {
+                DedupKey::Span => None,
+                DedupKey::SpanAndName => Some(extraction.name.to_string()),
+            };
+            seen.insert((extraction.start_byte, extraction.end_byte, name))
+        });
+    }
+
+    /// Group the matches by capture name, keeping their order within each group
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @name) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn a(){} fn b(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let groups = extracted.by_capture();
+    /// let names: Vec<_> = groups["name"].iter().map(|m| m.text.as_str()).collect();
+    /// assert_eq!(names, vec!["a", "b"]);
+    /// assert_eq!(groups["function"].len(), 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn by_capture(&self) -> HashMap<&str, Vec<&ExtractedMatch<'query>>> {
+        let mut groups: HashMap<&str, Vec<&ExtractedMatch<'query>>> = HashMap::new();
+        for extraction in &self.matches {
+            groups
+                .entry(extraction.name.as_ref())
+                .or_default()
+                .push(extraction);
+        }
+        groups
+    }
+
+    /// Get the names of the captures having at least one match in the file
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function (struct_item) @struct")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn a(){} fn b(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let present = extracted.capture_names_present();
+    /// assert_eq!(present.len(), 2);
+    /// assert!(present.contains("id") && present.contains("function"));
+    /// assert!(!present.contains("struct"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn capture_names_present(&self) -> HashSet<&str> {
+        self.matches
+            .iter()
+            .map(|extraction| extraction.name.as_ref())
+            .collect()
+    }
+
+    /// Count the matches starting on each line, keyed by one-based line number
+    ///
+    /// Lines are one-based whatever `Extractor::with_one_based` says, as in
+    /// editor gutters. Lines without any match are left out.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(call_expression) @call").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main() {\n    a(); b();\n\n    c();\n}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let counts: Vec<_> = extracted.matches_per_line().into_iter().collect();
+    /// assert_eq!(counts, vec![(2, 2), (4, 1)]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn matches_per_line(&self) -> BTreeMap<usize, usize> {
+        let mut counts = BTreeMap::new();
+        for extraction in &self.matches {
+            *counts.entry(extraction.start.row + 1).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Nest the matches by span containment, a match being the child of the smallest match containing it
+    ///
+    /// Roots and children are ordered by position. A match with the same
+    /// span as another is nested under it, the one coming first in `matches`
+    /// being the parent, so the tree doesn't depend on anything else than
+    /// the order of `matches`. Matches overlapping without containment are
+    /// siblings, and so is a zero-width match at the very end of another.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(impl_item) @impl (function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"impl A { fn a(){} fn b(){} } fn c(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let tree = extracted.as_tree();
+    /// let roots: Vec<_> = tree.iter().map(|node| node.extraction.name.as_ref()).collect();
+    /// assert_eq!(roots, vec!["impl", "function"]);
+    /// assert_eq!(tree[0].children.len(), 2);
+    /// assert_eq!(tree[0].children[1].children[0].extraction.text, "b");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn as_tree(&self) -> Vec<MatchNode<'_, 'query>> {
+        let mut ordered: Vec<&ExtractedMatch<'query>> = self.matches.iter().collect();
+        // the sort is stable, so same spans keep the order of `matches`
+        ordered.sort_by_key(|extraction| (extraction.start_byte, Reverse(extraction.end_byte)));
+
+        let mut roots = Vec::new();
+        // the chain of matches containing the current one, innermost last
+        let mut open: Vec<MatchNode> = Vec::new();
+        for extraction in ordered {
+            while let Some(innermost) = open.last() {
+                let innermost = innermost.extraction;
+                // a zero-width match right at the end of another is next to
+                // it, not inside, unless both have the same span
+                let contained = extraction.end_byte <= innermost.end_byte
+                    && (extraction.start_byte < innermost.end_byte
+                        || extraction.start_byte == innermost.start_byte);
+                if contained {
+                    break;
+                }
+                let done = open.pop().unwrap();
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(done),
+                    None => roots.push(done),
+                }
+            }
+            open.push(MatchNode {
+                extraction,
+                children: Vec::new(),
+            });
+        }
+        while let Some(done) = open.pop() {
+            match open.last_mut() {
+                Some(parent) => parent.children.push(done),
+                None => roots.push(done),
+            }
+        }
+
+        roots
+    }
+
+    /// Write the extracted file like `Display` does, colored with ANSI escapes when `use_color` is set
+    ///
+    /// Colors follow ripgrep: the file name is magenta, the coordinates are
+    /// green and the capture name is bold red. Deciding whether the output is
+    /// a terminal is left to the caller.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let mut out = Vec::new();
+    /// extracted.write_colored(&mut out, true)?;
+    /// assert_eq!(
+    ///     String::from_utf8(out)?,
+    ///     "\x1b[35mNO FILE\x1b[0m:\x1b[32m1\x1b[0m:\x1b[32m4\x1b[0m:\x1b[1;31mid\x1b[0m:main\n"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_colored<W: Write>(&self, mut out: W, use_color: bool) -> Result<()> {
+        if !use_color {
+            return write!(out, "{}", self).context("could not write lines");
+        }
+
+        const FILENAME: &str = "\x1b[35m";
+        const POSITION: &str = "\x1b[32m";
+        const NAME: &str = "\x1b[1;31m";
+        const RESET: &str = "\x1b[0m";
+
+        let filename = self.filename();
+        for extraction in &self.matches {
+            // every number is colored on its own, not the separators
+            let separator = format!("{}:{}", RESET, POSITION);
+            writeln!(
+                out,
+                "{}{}{}:{}{}{}:{}{}{}:{}",
+                FILENAME,
+                filename,
+                RESET,
+                POSITION,
+                extraction.display_position(&separator),
+                RESET,
+                NAME,
+                extraction.name,
+                RESET,
+                extraction.display_text()
+            )
+            .context("could not write lines")?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the fields of `Display` separated by NUL bytes, each record ending with two NUL bytes
+    ///
+    /// Unlike the `:`-separated lines, this can be split back safely even when
+    /// file names or captured text contain colons or newlines, as with
+    /// `grep -Z` and `xargs -0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let mut out = Vec::new();
+    /// extracted.write_null_delimited(&mut out)?;
+    /// assert_eq!(out, b"NO FILE\x001\x004\x00id\x00main\x00\x00");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_null_delimited<W: Write>(&self, mut out: W) -> Result<()> {
+        let filename = self.filename();
+        for extraction in &self.matches {
+            write!(
+                out,
+                "{}\0{}\0{}\0{}\0{}\0\0",
+                filename,
+                extraction.start.row + extraction.base(),
+                extraction.start.column + extraction.base(),
+                extraction.name,
+                extraction.text
+            )
+            .context("could not write records")?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the lines of `Display` with `separator` between the fields instead of `:`
+    ///
+    /// `:` is ambiguous with Windows drive letters (`C:\...`) and with colons
+    /// in the captured text; a tab gives TSV that spreadsheets and databases
+    /// import directly. The text is the one `Display` shows, so build the
+    /// Extractor `with_single_line_text` to keep each match on one line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(Some(Path::new("C:/src/main.rs")), b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let mut out = Vec::new();
+    /// extracted.write_separated(&mut out, "\t")?;
+    /// assert_eq!(String::from_utf8(out)?, "C:/src/main.rs\t1\t4\tid\tmain\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_separated<W: Write>(&self, mut out: W, separator: &str) -> Result<()> {
+        let filename = self.filename();
+        for extraction in &self.matches {
+            writeln!(
+                out,
+                "{}{sep}{}{sep}{}{sep}{}",
+                filename,
+                extraction.display_position(separator),
+                extraction.name,
+                extraction.display_text(),
+                sep = separator
+            )
+            .context("could not write lines")?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the extracted file in the quickfix format of Vim and Emacs
+    ///
+    /// One `file:line:col: name: text` line per match, which both Vim's default
+    /// `errorformat` (`:cfile`) and Emacs' compilation-mode understand. Editors
+    /// count from one, so lines and columns are one-based whatever
+    /// `Extractor::with_one_based` says, and only the first line of
+    /// multi-line text is kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item) @function").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main() {\n}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let mut out = Vec::new();
+    /// extracted.write_quickfix(&mut out)?;
+    /// assert_eq!(String::from_utf8(out)?, "NO FILE:1:1: function: fn main() {\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_quickfix<W: Write>(&self, mut out: W) -> Result<()> {
+        let filename = self.filename();
+        for extraction in &self.matches {
+            writeln!(
+                out,
+                "{}:{}:{}: {}: {}",
+                filename,
+                extraction.start.row + 1,
+                extraction.start.column + 1,
+                extraction.name,
+                extraction.text.lines().next().unwrap_or_default()
+            )
+            .context("could not write quickfix lines")?;
+        }
+
+        Ok(())
+    }
+
+    /// Serialize the extracted file to JSON, pretty-printed for humans or compact for pipelines
+    ///
+    /// See `to_json` for a slice of extracted files.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.to_json(false)?.lines().count(), 1);
+    /// assert!(extracted.to_json(true)?.lines().count() > 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_json(&self, pretty: bool) -> Result<String> {
+        if pretty {
+            serde_json::to_string_pretty(self).context("could not serialize to JSON")
+        } else {
+            serde_json::to_string(self).context("could not serialize to JSON")
+        }
+    }
+
+    /// Write the extracted file as a single line of JSON (NDJSON)
+    ///
+    /// Every call writes one self-contained JSON object followed by a newline,
+    /// so results can be streamed as files are processed instead of being
+    /// buffered into one big array. Field names are the same as in the array form.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let mut out = Vec::new();
+    /// extracted.write_ndjson(&mut out)?;
+    /// let line = String::from_utf8(out)?;
+    /// assert!(line.ends_with('\n'));
+    /// assert_eq!(line.lines().count(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_ndjson<W: Write>(&self, mut out: W) -> Result<()> {
+        serde_json::to_writer(&mut out, self).context("could not write JSON output")?;
+        writeln!(out).context("could not write line")
+    }
+}
+
+impl<'query> Display for ExtractedFile<'query> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let filename = self.filename();
+
+        for extraction in &self.matches {
+            writeln!(
+                f,
+                "{}:{}:{}:{}",
+                filename,
+                extraction.display_position(":"),
+                extraction.name,
+                extraction.display_text()
+            )?
+        }
+
+        Ok(())
+    }
+}
+
+/// A match borrowing its text from the source, see `Extractor::extract_borrowed`
+///
+/// The fields are those of `ExtractedMatch`, with the raw zero-based
+/// tree_sitter coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorrowedMatch<'query, 'src> {
+    /// Node kind, such as `function_item`
+    pub kind: &'static str,
+    /// Capture name
+    pub name: &'query str,
+    /// Captured text, a slice of the source
+    pub text: &'src str,
+    /// Start coordinate of the text
+    pub start: Point,
+    /// End coordinate of the text
+    pub end: Point,
+    /// Start byte offset of the text in the source
+    pub start_byte: usize,
+    /// End byte offset (exclusive) of the text in the source
+    pub end_byte: usize,
+    /// Index of the query that produced the match
+    pub query_index: usize,
+    /// Index of the query match that produced the match, among the matches of its query
+    pub match_index: usize,
+    /// Index of the pattern that produced the match within its query
+    pub pattern_index: usize,
+}
+
+/// A match and the matches it contains, see `ExtractedFile::as_tree`
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct MatchNode<'file, 'query> {
+    /// Containing match
+    pub extraction: &'file ExtractedMatch<'query>,
+    /// Matches contained in `extraction` and in none of the others, by position
+    pub children: Vec<MatchNode<'file, 'query>>,
+}
+
+/// What makes two matches duplicates of each other, see `ExtractedFile::dedup`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupKey {
+    /// Same start and end
+    Span,
+    /// Same start, end and capture name
+    SpanAndName,
+}
+
+/// Pattern matching nodes in a syntax tree.
+///
+/// `start` and `end` hold the zero-based tree_sitter coordinates. They are
+/// shown and serialized one-based unless the Extractor was built
+/// `with_one_based(false)`, in which case the serialized points say so.
+///
+/// Matches are compared on what they serialize: the display settings of
+/// the Extractor (`with_one_based`, `with_max_text_len`...) and the label
+/// don't count, so the same match extracted with other settings is equal.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ExtractedMatch<'query> {
+    /// Pattern type, serialized as `kind`
+    kind: Cow<'static, str>,
+    /// Pattern name
+    pub name: Cow<'query, str>,
+    /// Fragment program
+    pub text: String,
+    /// Start cordinate of current text
+    #[serde(deserialize_with = "deserialize_point")]
+    #[cfg_attr(feature = "schema", schemars(with = "SchemaPoint"))]
+    pub start: Point,
     /// End cordinate of current text
-    #[serde(serialize_with = "serialize_point")]
+    #[serde(deserialize_with = "deserialize_point")]
+    #[cfg_attr(feature = "schema", schemars(with = "SchemaPoint"))]
     pub end: Point,
+    /// Start byte offset of current text in the source, without its BOM (see `raw_bytes`)
+    pub start_byte: usize,
+    /// End byte offset (exclusive) of current text in the source, without its BOM
+    pub end_byte: usize,
+    /// Index of the query that produced the match, see `Extractor::from_queries`
+    #[serde(default)]
+    pub query_index: usize,
+    /// Index of the query match that produced the match, among the matches of
+    /// its query. Captures of the same query match share it, so they can be
+    /// grouped back with `query_index`
+    #[serde(default)]
+    pub match_index: usize,
+    /// Index of the pattern that produced the match within its query, in
+    /// the order the patterns are written
+    #[serde(default)]
+    pub pattern_index: usize,
+    /// Node kind of the parent of the captured node, `None` for the root node
+    #[serde(default)]
+    pub parent_kind: Option<Cow<'static, str>>,
+    /// Field name of the captured node in its parent, such as `name` or
+    /// `body`, `None` if it isn't in a field
+    #[serde(default)]
+    pub field_name: Option<Cow<'static, str>>,
+    /// Text of the label capture of the same query match, see
+    /// `Extractor::with_label_capture`. Not serialized
+    #[serde(skip)]
+    pub label: Option<String>,
+    /// Whether coordinates are shown one-based or not
+    #[serde(skip, default = "default_one_based")]
+    one_based: bool,
+    /// Most chars of text shown, if truncated
+    #[serde(skip)]
+    max_text_len: Option<usize>,
+    /// Whether text is shown on a single line
+    #[serde(skip)]
+    single_line_text: bool,
+    /// Whether the escaped text is serialized too
+    #[serde(skip)]
+    escaped_text: bool,
+    /// Whether the end is shown too
+    #[serde(skip)]
+    end_position: bool,
+}
+
+impl<'query> ExtractedMatch<'query> {
+    /// Get the tree_sitter node kind of the match, such as `function_item` or `identifier`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(function_item (identifier) @id) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].kind(), "function_item");
+    /// assert_eq!(extracted.matches[1].kind(), "identifier");
+    /// assert_eq!(extracted.matches[1].parent_kind.as_deref(), Some("function_item"));
+    /// assert_eq!(extracted.matches[1].field_name.as_deref(), Some("name"));
+    /// assert_eq!(extracted.matches[0].field_name, None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Get the length of the captured text in bytes, from its byte offsets
+    ///
+    /// It doesn't need the text, so it holds with `Extractor::with_skip_text` too.
+    pub fn byte_len(&self) -> usize {
+        self.end_byte - self.start_byte
+    }
+
+    /// Get the length of the captured text in chars
+    ///
+    /// It is counted on `text`, so it is 0 when the text is skipped (see
+    /// `Extractor::with_skip_text`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(string_literal) @string").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, "const S: &str = \"héllo\";".as_bytes(), &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].byte_len(), 8);
+    /// assert_eq!(extracted.matches[0].char_count(), 7);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// Get the captured bytes of `source`, whether they are valid UTF-8 or not
+    ///
+    /// Unlike `text`, the bytes aren't decoded, so a capture holding invalid
+    /// UTF-8 (a byte string literal with odd bytes, say) can be looked at as
+    /// it is, where `with_lossy_text` would replace them. `source` is the
+    /// program the match was extracted from; like the extraction, this
+    /// skips its BOM, which byte offsets don't count. A source decoded by
+    /// `Extractor::with_encoding` has no bytes of its own to give, its
+    /// matches only have their `text`.
+    ///
+    /// # Panics
+    ///
+    /// If `source` is shorter than the end of the match.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let source = b"// \xff\xfe\nfn main(){}";
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(line_comment) @comment").unwrap();
+    /// let extractor = Extractor::new(lang, query).with_lossy_text(true);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, source, &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert!(extracted.matches[0].raw_bytes(source).starts_with(b"// \xff\xfe"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw_bytes<'src>(&self, source: &'src [u8]) -> &'src [u8] {
+        &skip_bom(source)[self.start_byte..self.end_byte]
+    }
+
+    /// Get the names of the declarations enclosing the match, outermost first, joined with `::`
+    ///
+    /// Every ancestor of the captured node with a `name` field (modules,
+    /// functions, structs, traits...) contributes its name; `impl` blocks
+    /// contribute their type. The captured node itself isn't part of the path.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - the tree the match was extracted from, see `Extractor::parse`
+    ///
+    /// * `source` - fragment program, its BOM is skipped as by `Extractor::parse`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let source = b"mod foo { impl Bar { fn baz() { let x = 1; } } }";
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(let_declaration) @let").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let mut parser = Parser::new();
+    /// let tree = extractor.parse(source, &mut parser)?;
+    /// let extracted = extractor.extract_from_text(None, source, &mut parser)?.unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].scope_path(&tree, source), "foo::Bar::baz");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scope_path(&self, tree: &Tree, source: &[u8]) -> String {
+        let source = skip_bom(source);
+        let mut names = Vec::new();
+        let mut current = tree
+            .root_node()
+            .descendant_for_byte_range(self.start_byte, self.end_byte);
+
+        while let Some(node) = current {
+            current = node.parent();
+            // the descendant found may be the captured node or one of its
+            // children with the same span, neither of them is a scope
+            if node.byte_range() == (self.start_byte..self.end_byte) {
+                continue;
+            }
+
+            let name = if node.kind() == "impl_item" {
+                node.child_by_field_name("type")
+            } else {
+                node.child_by_field_name("name")
+            };
+            if let Some(name) = name {
+                names.push(String::from_utf8_lossy(&source[name.byte_range()]).into_owned());
+            }
+        }
+
+        names.reverse();
+        names.join("::")
+    }
+
+    /// Get up to `before` source lines preceding the match and up to `after` lines following it, like `grep -C`
+    ///
+    /// Fewer lines are returned when the match is close to the start or the end
+    /// of the source.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let source = b"// one\n// two\nfn main() {\n}\n// three\n";
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item) @function").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, source, &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let (before, after) = extracted.matches[0].context(source, 1, 5);
+    /// assert_eq!(before, vec!["// two"]);
+    /// assert_eq!(after, vec!["// three"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn context(
+        &self,
+        source: &[u8],
+        before: usize,
+        after: usize,
+    ) -> (Vec<String>, Vec<String>) {
+        let source = String::from_utf8_lossy(skip_bom(source));
+        let lines: Vec<&str> = source.lines().collect();
+
+        let first = self.start.row.saturating_sub(before);
+        let before_lines = lines
+            .iter()
+            .skip(first)
+            .take(self.start.row - first)
+            .map(|line| line.to_string())
+            .collect();
+        // a capture ending with its newline ends at the start of the next
+        // row, which is then the first line after it
+        let first_after = if self.end.column == 0 && self.end.row > self.start.row {
+            self.end.row
+        } else {
+            self.end.row + 1
+        };
+        let after_lines = lines
+            .iter()
+            .skip(first_after)
+            .take(after)
+            .map(|line| line.to_string())
+            .collect();
+
+        (before_lines, after_lines)
+    }
+
+    /// Get the captured text cut to `max_chars` chars, followed by `…` if it was longer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(string_literal) @string").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, "const S: &str = \"héllo\";".as_bytes(), &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].truncated_text(3), "\"hé…");
+    /// assert_eq!(extracted.matches[0].truncated_text(7), "\"héllo\"");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn truncated_text(&self, max_chars: usize) -> Cow<'_, str> {
+        truncate(&self.text, max_chars)
+    }
+
+    /// Get the captured text on a single line, every run of whitespace (newlines included) squeezed to one space
+    ///
+    /// Leading and trailing whitespace is removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(block) @body").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main() {\n    let x = 1;\n\n    run(x);\n}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].single_line_text(), "{ let x = 1; run(x); }");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn single_line_text(&self) -> String {
+        self.text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Get the captured text with control characters, backslashes and
+    /// whitespace other than spaces escaped like in Rust strings
+    ///
+    /// Newlines and tabs become `\n` and `\t`, other such characters
+    /// `\u{...}`, so the text always fits on one line.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(line_comment) @comment").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, "// a\tb\u{a0}c\\".as_bytes(), &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.matches[0].escaped_text(), "// a\\tb\\u{a0}c\\\\");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn escaped_text(&self) -> String {
+        let mut escaped = String::with_capacity(self.text.len());
+        for c in self.text.chars() {
+            if c == '\\' || c.is_control() {
+                escaped.extend(c.escape_debug());
+            } else if c.is_whitespace() && c != ' ' {
+                escaped.extend(c.escape_unicode());
+            } else {
+                escaped.push(c);
+            }
+        }
+        escaped
+    }
+
+    /// Get the text shown by `Display`: the label if any, following `Extractor::with_single_line_text` and `with_max_text_len`
+    pub(crate) fn display_text(&self) -> Cow<'_, str> {
+        let text = match &self.label {
+            Some(label) => Cow::Borrowed(label.as_str()),
+            None if self.single_line_text => Cow::Owned(self.single_line_text()),
+            None => Cow::Borrowed(self.text.as_str()),
+        };
+
+        match self.max_text_len {
+            Some(max_chars) => Cow::Owned(truncate(&text, max_chars).into_owned()),
+            None => text,
+        }
+    }
+
+    /// Get the position shown by `Display`, `row:col` or `row:col-end_row:end_col` following `Extractor::with_end_position`
+    pub(crate) fn display_position(&self, separator: &str) -> String {
+        let start = format!(
+            "{}{}{}",
+            self.start.row + self.base(),
+            separator,
+            self.start.column + self.base()
+        );
+        if !self.end_position {
+            return start;
+        }
+
+        format!(
+            "{}-{}{}{}",
+            start,
+            self.end.row + self.base(),
+            separator,
+            self.end.column + self.base()
+        )
+    }
+
+    /// Get the offset added to the zero-based coordinates when they are shown
+    pub(crate) fn base(&self) -> usize {
+        usize::from(self.one_based)
+    }
+
+    /// Get the serialized fields, which matches are compared on
+    #[allow(clippy::type_complexity)]
+    fn data(
+        &self,
+    ) -> (
+        &str,
+        &str,
+        &str,
+        Point,
+        Point,
+        usize,
+        usize,
+        usize,
+        usize,
+        usize,
+        Option<&str>,
+        Option<&str>,
+    ) {
+        (
+            &self.kind,
+            &self.name,
+            &self.text,
+            self.start,
+            self.end,
+            self.start_byte,
+            self.end_byte,
+            self.query_index,
+            self.match_index,
+            self.pattern_index,
+            self.parent_kind.as_deref(),
+            self.field_name.as_deref(),
+        )
+    }
 }
 
-fn serialize_point<S>(point: &Point, sz: S) -> Result<S::Ok, S::Error>
+impl<'query> PartialEq for ExtractedMatch<'query> {
+    fn eq(&self, other: &Self) -> bool {
+        self.data() == other.data()
+    }
+}
+
+impl<'query> Eq for ExtractedMatch<'query> {}
+
+impl<'query> PartialOrd for ExtractedMatch<'query> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'query> Ord for ExtractedMatch<'query> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.data().cmp(&other.data())
+    }
+}
+
+impl<'query> Serialize for ExtractedMatch<'query> {
+    fn serialize<S>(&self, sz: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut out = sz.serialize_struct("ExtractedMatch", 12 + usize::from(self.escaped_text))?;
+        out.serialize_field("kind", &self.kind)?;
+        out.serialize_field("name", &self.name)?;
+        out.serialize_field("text", &self.text)?;
+        if self.escaped_text {
+            out.serialize_field("text_escaped", &self.escaped_text())?;
+        }
+        out.serialize_field("start", &BasedPoint(&self.start, self.base()))?;
+        out.serialize_field("end", &BasedPoint(&self.end, self.base()))?;
+        out.serialize_field("start_byte", &self.start_byte)?;
+        out.serialize_field("end_byte", &self.end_byte)?;
+        out.serialize_field("query_index", &self.query_index)?;
+        out.serialize_field("match_index", &self.match_index)?;
+        out.serialize_field("pattern_index", &self.pattern_index)?;
+        out.serialize_field("parent_kind", &self.parent_kind)?;
+        out.serialize_field("field_name", &self.field_name)?;
+        out.end()
+    }
+}
+
+/// A tree_sitter `Point` shifted by a base when serialized
+struct BasedPoint<'point>(&'point Point, usize);
+
+impl<'point> Serialize for BasedPoint<'point> {
+    fn serialize<S>(&self, sz: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let BasedPoint(point, base) = self;
+        let mut out = sz.serialize_struct("Point", 2 + usize::from(*base == 0))?;
+        out.serialize_field("row", &(point.row + base))?;
+        out.serialize_field("column", &(point.column + base))?;
+        // one-based is the default, zero-based points are labelled so that
+        // they aren't read back one-based
+        if *base == 0 {
+            out.serialize_field("base", base)?;
+        }
+        out.end()
+    }
+}
+
+/// Shape of a `Point` serialized by `BasedPoint`, for the JSON Schema
+#[cfg(feature = "schema")]
+#[derive(schemars::JsonSchema)]
+#[schemars(rename = "Point")]
+#[allow(dead_code)]
+struct SchemaPoint {
+    /// Row, one-based unless the Extractor was built `with_one_based(false)`
+    row: usize,
+    /// Byte offset in the row, one-based unless the Extractor was built `with_one_based(false)`
+    column: usize,
+    /// 0 when the Extractor was built `with_one_based(false)`, left out otherwise
+    base: Option<usize>,
+}
+
+/// Read back a `Point` serialized by `BasedPoint`, one-based unless it says otherwise
+fn deserialize_point<'de, D>(dz: D) -> Result<Point, D::Error>
 where
-    S: Serializer,
+    D: Deserializer<'de>,
 {
-    let mut out = sz.serialize_struct("Point", 2)?;
-    out.serialize_field("row", &(point.row + 1))?;
-    out.serialize_field("column", &(point.column + 1))?;
-    out.end()
+    #[derive(Deserialize)]
+    struct SerializedPoint {
+        row: usize,
+        column: usize,
+        #[serde(default = "default_base")]
+        base: usize,
+    }
+
+    let point = SerializedPoint::deserialize(dz)?;
+    if point.base > 1 {
+        return Err(de::Error::custom(format!(
+            "expected a base of 0 or 1, got {}",
+            point.base
+        )));
+    }
+    match (
+        point.row.checked_sub(point.base),
+        point.column.checked_sub(point.base),
+    ) {
+        (Some(row), Some(column)) => Ok(Point::new(row, column)),
+        // a zero row or column can't be one-based, the point was likely
+        // written by hand without its base
+        _ => Err(de::Error::custom(format!(
+            "expected one-based coordinates, got {}:{}",
+            point.row, point.column
+        ))),
+    }
+}
+
+fn default_base() -> usize {
+    1
+}
+
+fn default_one_based() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn extractor_is_send_sync() {
+        assert_send_sync::<Extractor>();
+        assert_send_sync::<ExtractedFile>();
+    }
+
+    #[test]
+    fn extract_skips_bom() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id)")
+            .unwrap();
+        let extractor = Extractor::new(lang, query);
+        let extracted = extractor
+            .extract_from_text(None, b"\xef\xbb\xbffn main(){}", &mut Parser::new())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(extracted.matches[0].start, Point::new(0, 3));
+        assert_eq!(extracted.matches[0].start_byte, 3);
+        assert_eq!(extracted.matches[0].text, "main");
+    }
+
+    #[test]
+    fn bom_is_skipped_everywhere() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id)")
+            .unwrap();
+        let extractor = Extractor::new(lang, query);
+        let source = b"\xef\xbb\xbffn main(){}";
+        let mut parser = Parser::new();
+
+        let extracted = extractor
+            .extract_from_text(None, source, &mut parser)
+            .unwrap()
+            .unwrap();
+        assert_eq!(extracted.matches[0].raw_bytes(source), b"main");
+
+        let tree = extractor.parse(source, &mut parser).unwrap();
+        let mut cursors = extractor.query_cursors();
+        let iterated: Vec<_> = extractor
+            .matches_iter(&tree, source, &mut cursors)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(iterated, extracted.matches);
+
+        let sexps = extractor.sexp_for_matches(source, &mut parser).unwrap();
+        assert_eq!(sexps, vec![("id".to_string(), "(identifier)".to_string())]);
+
+        let rewritten = extractor
+            .rewrite(source, &mut parser, |m| Some(m.text.to_uppercase()))
+            .unwrap();
+        assert_eq!(rewritten, "fn MAIN(){}");
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn decode_sources_everywhere() {
+        // "fn main(){}" in UTF-16LE, with a BOM
+        let mut source = vec![0xff, 0xfe];
+        source.extend(
+            "fn main(){}"
+                .encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes()),
+        );
+        let path = std::env::temp_dir().join("rust_hero_decode_sources_everywhere.rs");
+        fs::write(&path, &source).unwrap();
+
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id)")
+            .unwrap();
+        let extractor = Extractor::new(lang, query).with_encoding(SourceEncoding::Detect);
+        let mut parser = Parser::new();
+
+        assert_eq!(extractor.count_matches(&source, &mut parser).unwrap(), 1);
+        assert!(extractor.is_match(&source, &mut parser).unwrap());
+        let paths = vec![path];
+        let (matching, errors) = extractor.files_with_matches(&paths, &mut parser);
+        assert_eq!((matching, errors.len()), (paths.clone(), 0));
+        let (missing, errors) = extractor.files_without_matches(&paths, &mut parser);
+        assert_eq!((missing.len(), errors.len()), (0, 0));
+    }
+
+    #[test]
+    fn extract_counts_source_size() {
+        let lang = Language::Rust;
+        let query = lang.parse_query("(function_item) @function").unwrap();
+        let extractor = Extractor::new(lang, query);
+
+        let extracted = extractor
+            .extract_from_text(None, b"fn a(){}\nfn b(){}\n", &mut Parser::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!((extracted.source_lines, extracted.source_bytes), (2, 18));
+
+        let extracted = extractor
+            .extract_from_text(None, b"fn a(){}\n\nfn b(){}", &mut Parser::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!((extracted.source_lines, extracted.source_bytes), (3, 18));
+    }
+
+    #[test]
+    fn rewrite_overlapping_matches() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id) @function")
+            .unwrap();
+        let extractor = Extractor::new(lang, query);
+
+        let err = extractor
+            .rewrite(b"fn main(){}", &mut Parser::new(), |_| Some(String::new()))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "could not rewrite overlapping matches at bytes 0..11 and 3..7"
+        );
+    }
+
+    #[test]
+    fn extract_with_missing_cursors() {
+        let lang = Language::Rust;
+        let extractor = Extractor::from_queries(
+            lang,
+            vec![
+                lang.parse_query("(function_item) @function").unwrap(),
+                lang.parse_query("(identifier) @id").unwrap(),
+            ],
+        );
+
+        let err = extractor
+            .extract_with_cursors(
+                None,
+                b"fn main(){}",
+                &mut Parser::new(),
+                &mut [QueryCursor::new()],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "got 1 query cursors for 2 queries, see `query_cursors`"
+        );
+    }
+
+    #[test]
+    fn extract_crlf_positions() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id) @function")
+            .unwrap();
+        let extractor = Extractor::new(lang, query);
+        let extracted = extractor
+            .extract_from_text(
+                None,
+                b"// a\r\n\r\nfn main() {\r\n}\r\n",
+                &mut Parser::new(),
+            )
+            .unwrap()
+            .unwrap();
+
+        let function = &extracted.matches[0];
+        assert_eq!(function.text, "fn main() {\r\n}");
+        assert_eq!(
+            (function.start, function.end),
+            (Point::new(2, 0), Point::new(3, 1))
+        );
+
+        let id = &extracted.matches[1];
+        assert_eq!(id.text, "main");
+        assert_eq!((id.start, id.end), (Point::new(2, 3), Point::new(2, 7)));
+        assert_eq!((id.start_byte, id.end_byte), (11, 15));
+        assert_eq!(extracted.source_lines, 4);
+    }
+
+    #[test]
+    fn extract_trims_crlf_line_ends() {
+        let lang = Language::Rust;
+        let query = lang.parse_query("(line_comment) @comment").unwrap();
+        let source = b"fn main() {\r\n    // one\r\n    // two\r\n}\r\n";
+
+        let extractor = Extractor::new(lang, query);
+        let kept = extractor
+            .extract_from_text(None, source, &mut Parser::new())
+            .unwrap()
+            .unwrap();
+        for comment in &kept.matches {
+            // whether the grammar takes the `\r` or not, text and span agree
+            assert_eq!(
+                comment.end.column - comment.start.column,
+                comment.text.len()
+            );
+            assert_eq!(comment.end_byte - comment.start_byte, comment.text.len());
+        }
+
+        let extractor = extractor.with_trim_cr(true);
+        let trimmed = extractor
+            .extract_from_text(None, source, &mut Parser::new())
+            .unwrap()
+            .unwrap();
+        let texts: Vec<_> = trimmed.matches.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, vec!["// one", "// two"]);
+        assert_eq!(trimmed.matches[1].end, Point::new(2, 10));
+        assert_eq!(trimmed.matches[1].end_byte, 35);
+    }
+
+    #[test]
+    fn extract_skips_text_but_still_filters_it() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id)")
+            .unwrap();
+        let source = b"fn test_one(){}\nfn main(){}\nfn test_two(){}\n";
+
+        let extractor = Extractor::new(lang, query)
+            .with_skip_text(true)
+            .with_text_filter(Regex::new("^test_").unwrap(), None);
+        let extracted = extractor
+            .extract_from_text(None, source, &mut Parser::new())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(extracted.matches.len(), 2);
+        assert!(extracted.matches.iter().all(|m| m.text.is_empty()));
+        assert_eq!(extracted.matches[0].byte_len(), "test_one".len());
+        assert_eq!(extracted.matches[0].char_count(), 0);
+        assert_eq!(extracted.matches[1].start, Point::new(2, 3));
+        assert_eq!(extracted.matches[1].end_byte, 39);
+    }
+
+    #[test]
+    fn extract_orders_same_span_captures_outer_first() {
+        let lang = Language::Rust;
+        // the whole source is the function, so both nodes have the same span
+        let source = b"fn main(){}";
+
+        for raw in [
+            "(source_file (function_item) @inner) @outer",
+            "(source_file (function_item) @outer) @inner",
+        ] {
+            let extractor = Extractor::new(lang, lang.parse_query(raw).unwrap());
+            let extracted = extractor
+                .extract_from_text(None, source, &mut Parser::new())
+                .unwrap()
+                .unwrap();
+
+            let kinds: Vec<_> = extracted.matches.iter().map(|m| m.kind()).collect();
+            assert_eq!(kinds, vec!["source_file", "function_item"], "{}", raw);
+            assert_eq!(extracted.matches[0].end_byte, extracted.matches[1].end_byte);
+        }
+    }
+
+    #[test]
+    fn extract_orders_captures_by_byte_range() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item name: (identifier) @name body: (block) @body) @function")
+            .unwrap();
+        let extractor = Extractor::new(lang, query);
+        let extracted = extractor
+            .extract_from_text(None, b"fn main() { run(); }", &mut Parser::new())
+            .unwrap()
+            .unwrap();
+
+        let names: Vec<_> = extracted.matches.iter().map(|m| m.name.as_ref()).collect();
+        assert_eq!(names, vec!["function", "name", "body"]);
+    }
+
+    #[test]
+    fn extract_orders_captures_of_one_node_by_name() {
+        let lang = Language::Rust;
+        let query = lang.parse_query("(identifier) @second @first").unwrap();
+        let extractor = Extractor::new(lang, query);
+        let extracted = extractor
+            .extract_from_text(None, b"fn main(){}", &mut Parser::new())
+            .unwrap()
+            .unwrap();
+
+        let names: Vec<_> = extracted.matches.iter().map(|m| m.name.as_ref()).collect();
+        assert_eq!(names, vec!["second", "first"]);
+    }
+
+    #[test]
+    fn matches_compare_whatever_the_display_settings() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id)")
+            .unwrap();
+        let source = b"fn main(){}";
+        let plain = Extractor::new(lang, query);
+        let expected = plain
+            .extract_from_text(None, source, &mut Parser::new())
+            .unwrap()
+            .unwrap();
+
+        let query = lang
+            .parse_query("(function_item (identifier) @id)")
+            .unwrap();
+        let zero_based = Extractor::new(lang, query)
+            .with_one_based(false)
+            .with_max_text_len(2)
+            .with_end_position(true);
+        let extracted = zero_based
+            .extract_from_text(None, source, &mut Parser::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(extracted, expected);
+
+        let json = serde_json::to_string(&extracted).unwrap();
+        assert!(json.contains(r#""start":{"row":0,"column":3,"base":0}"#));
+        let cached: ExtractedFile<'static> = serde_json::from_str(&json).unwrap();
+        assert_eq!(cached.matches[0].start, Point::new(0, 3));
+        assert_eq!(cached, expected);
+    }
+
+    #[test]
+    fn deserialize_rejects_zero_one_based_points() {
+        let json = r#"{"kind":"identifier","name":"id","text":"main","start":{"row":0,"column":3},"end":{"row":1,"column":8},"start_byte":3,"end_byte":7}"#;
+
+        let err = serde_json::from_str::<ExtractedMatch>(json).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected one-based coordinates, got 0:3"));
+    }
+
+    #[test]
+    fn as_tree_keeps_zero_width_matches_at_the_end_as_siblings() {
+        let lang = Language::Rust;
+        let query = lang.parse_query("(function_item) @function").unwrap();
+        let extractor = Extractor::new(lang, query);
+        let mut extracted = extractor
+            .extract_from_text(None, b"fn a(){} fn b(){}", &mut Parser::new())
+            .unwrap()
+            .unwrap();
+
+        // a zero-width match where `a` ends, and one where `b` starts
+        let empty = |at: usize| ExtractedMatch {
+            text: String::new(),
+            start: Point::new(0, at),
+            end: Point::new(0, at),
+            start_byte: at,
+            end_byte: at,
+            ..ExtractedMatch::deserialize(serde_json::to_value(&extracted.matches[0]).unwrap())
+                .unwrap()
+        };
+        extracted.matches.push(empty(8));
+        extracted.matches.push(empty(9));
+
+        let tree = extracted.as_tree();
+        let roots: Vec<_> = tree
+            .iter()
+            .map(|node| (node.extraction.start_byte, node.extraction.end_byte))
+            .collect();
+        assert_eq!(roots, vec![(0, 8), (8, 8), (9, 17)]);
+        assert!(tree[0].children.is_empty());
+        assert_eq!(tree[2].children.len(), 1);
+    }
+
+    #[test]
+    fn context_after_a_capture_ending_with_a_newline() {
+        let lang = Language::Rust;
+        let query = lang.parse_query("(line_comment) @comment").unwrap();
+        let source = b"// one\n// two\nfn main(){}\n";
+        let extractor = Extractor::new(lang, query);
+        let extracted = extractor
+            .extract_from_text(None, source, &mut Parser::new())
+            .unwrap()
+            .unwrap();
+
+        // the match of a grammar taking the newline along
+        let mut comment =
+            ExtractedMatch::deserialize(serde_json::to_value(&extracted.matches[0]).unwrap())
+                .unwrap();
+        comment.text = "// one\n".to_string();
+        comment.end = Point::new(1, 0);
+        comment.end_byte = 7;
+
+        let (_, after) = comment.context(source, 0, 1);
+        assert_eq!(after, vec!["// two"]);
+        // and whatever this grammar does, the next line follows
+        let (_, after) = extracted.matches[0].context(source, 0, 1);
+        assert_eq!(after, vec!["// two"]);
+    }
 }
@@ -0,0 +1,311 @@
+#[cfg(feature = "encoding")]
+use crate::query::SourceEncoding;
+use crate::query::{Extractor, Language};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::path::PathBuf;
+use std::time::Duration;
+use tree_sitter::Query;
+
+/// Builder for an `Extractor` and its options
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{ExtractorBuilder,Language};
+/// use tree_sitter::Parser;
+///
+/// let extractor = ExtractorBuilder::new()
+///     .language(Language::Rust)
+///     .query_str("(function_item (identifier) @id) @function")
+///     .lossy_text(true)
+///     .build()?;
+/// let extracted = extractor
+///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+///     .unwrap();
+/// assert_eq!(extracted.matches.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct ExtractorBuilder {
+    /// Language of source code
+    language: Option<Language>,
+    /// Raw queries, parsed when the Extractor is built
+    query_strs: Vec<String>,
+    /// Already parsed queries
+    queries: Vec<Query>,
+    /// See `Extractor::with_one_based`
+    one_based: Option<bool>,
+    /// See `Extractor::with_max_text_len`
+    max_text_len: Option<usize>,
+    /// See `Extractor::with_single_line_text`
+    single_line_text: Option<bool>,
+    /// See `Extractor::with_escaped_text`
+    escaped_text: Option<bool>,
+    /// See `Extractor::with_end_position`
+    end_position: Option<bool>,
+    /// See `Extractor::with_lossy_text`
+    lossy_text: Option<bool>,
+    /// See `Extractor::with_trim_cr`
+    trim_cr: Option<bool>,
+    /// See `Extractor::with_skip_text`
+    skip_text: Option<bool>,
+    /// See `Extractor::with_ignore_underscore_captures`
+    ignore_underscore_captures: Option<bool>,
+    /// See `Extractor::with_timeout`
+    timeout: Option<Duration>,
+    /// See `Extractor::with_capture_filter`
+    capture_filter: Option<Vec<String>>,
+    /// See `Extractor::with_text_filter`
+    text_filter: Option<(Regex, Option<String>)>,
+    /// See `Extractor::with_max_matches`
+    max_matches: Option<usize>,
+    /// See `Extractor::with_match_limit`
+    match_limit: Option<u32>,
+    /// See `Extractor::with_label_capture`
+    label_capture: Option<String>,
+    /// See `Extractor::with_first_capture_only`
+    first_capture_only: Option<bool>,
+    /// See `Extractor::with_normalized_paths`
+    normalize_paths: Option<bool>,
+    /// See `Extractor::with_base_dir`
+    base_dir: Option<PathBuf>,
+    /// See `Extractor::with_mmap`
+    #[cfg(feature = "mmap")]
+    mmap: Option<bool>,
+    /// See `Extractor::with_encoding`
+    #[cfg(feature = "encoding")]
+    encoding: Option<SourceEncoding>,
+}
+
+impl ExtractorBuilder {
+    /// Build a new ExtractorBuilder without language nor query
+    pub fn new() -> ExtractorBuilder {
+        ExtractorBuilder::default()
+    }
+
+    /// Set the language of source code
+    pub fn language(mut self, language: Language) -> ExtractorBuilder {
+        self.language = Some(language);
+        self
+    }
+
+    /// Add a query to be parsed when building. Can be called several times, see `Extractor::from_queries`
+    pub fn query_str(mut self, raw: &str) -> ExtractorBuilder {
+        self.query_strs.push(raw.to_string());
+        self
+    }
+
+    /// Add an already parsed query. Can be called several times, see `Extractor::from_queries`
+    pub fn query(mut self, query: Query) -> ExtractorBuilder {
+        self.queries.push(query);
+        self
+    }
+
+    /// See `Extractor::with_one_based`
+    pub fn one_based(mut self, one_based: bool) -> ExtractorBuilder {
+        self.one_based = Some(one_based);
+        self
+    }
+
+    /// See `Extractor::with_max_text_len`
+    pub fn max_text_len(mut self, max_text_len: usize) -> ExtractorBuilder {
+        self.max_text_len = Some(max_text_len);
+        self
+    }
+
+    /// See `Extractor::with_single_line_text`
+    pub fn single_line_text(mut self, single_line_text: bool) -> ExtractorBuilder {
+        self.single_line_text = Some(single_line_text);
+        self
+    }
+
+    /// See `Extractor::with_escaped_text`
+    pub fn escaped_text(mut self, escaped_text: bool) -> ExtractorBuilder {
+        self.escaped_text = Some(escaped_text);
+        self
+    }
+
+    /// See `Extractor::with_end_position`
+    pub fn end_position(mut self, end_position: bool) -> ExtractorBuilder {
+        self.end_position = Some(end_position);
+        self
+    }
+
+    /// See `Extractor::with_lossy_text`
+    pub fn lossy_text(mut self, lossy_text: bool) -> ExtractorBuilder {
+        self.lossy_text = Some(lossy_text);
+        self
+    }
+
+    /// See `Extractor::with_trim_cr`
+    pub fn trim_cr(mut self, trim_cr: bool) -> ExtractorBuilder {
+        self.trim_cr = Some(trim_cr);
+        self
+    }
+
+    /// See `Extractor::with_skip_text`
+    pub fn skip_text(mut self, skip_text: bool) -> ExtractorBuilder {
+        self.skip_text = Some(skip_text);
+        self
+    }
+
+    /// See `Extractor::with_ignore_underscore_captures`
+    pub fn ignore_underscore_captures(mut self, ignore: bool) -> ExtractorBuilder {
+        self.ignore_underscore_captures = Some(ignore);
+        self
+    }
+
+    /// See `Extractor::with_timeout`
+    pub fn timeout(mut self, timeout: Duration) -> ExtractorBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// See `Extractor::with_capture_filter`
+    pub fn capture_filter(mut self, names: &[&str]) -> ExtractorBuilder {
+        self.capture_filter = Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// See `Extractor::with_text_filter`
+    pub fn text_filter(mut self, regex: Regex, capture: Option<&str>) -> ExtractorBuilder {
+        self.text_filter = Some((regex, capture.map(|name| name.to_string())));
+        self
+    }
+
+    /// See `Extractor::with_max_matches`
+    pub fn max_matches(mut self, max_matches: usize) -> ExtractorBuilder {
+        self.max_matches = Some(max_matches);
+        self
+    }
+
+    /// See `Extractor::with_match_limit`
+    pub fn match_limit(mut self, match_limit: u32) -> ExtractorBuilder {
+        self.match_limit = Some(match_limit);
+        self
+    }
+
+    /// See `Extractor::with_label_capture`
+    pub fn label_capture(mut self, name: &str) -> ExtractorBuilder {
+        self.label_capture = Some(name.to_string());
+        self
+    }
+
+    /// See `Extractor::with_first_capture_only`
+    pub fn first_capture_only(mut self, first_capture_only: bool) -> ExtractorBuilder {
+        self.first_capture_only = Some(first_capture_only);
+        self
+    }
+
+    /// See `Extractor::with_normalized_paths`
+    pub fn normalized_paths(mut self, normalize_paths: bool) -> ExtractorBuilder {
+        self.normalize_paths = Some(normalize_paths);
+        self
+    }
+
+    /// See `Extractor::with_base_dir`
+    pub fn base_dir(mut self, base_dir: PathBuf) -> ExtractorBuilder {
+        self.base_dir = Some(base_dir);
+        self
+    }
+
+    /// See `Extractor::with_mmap`
+    #[cfg(feature = "mmap")]
+    pub fn mmap(mut self, mmap: bool) -> ExtractorBuilder {
+        self.mmap = Some(mmap);
+        self
+    }
+
+    /// See `Extractor::with_encoding`
+    #[cfg(feature = "encoding")]
+    pub fn encoding(mut self, encoding: SourceEncoding) -> ExtractorBuilder {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Parse the queries and build the Extractor
+    pub fn build(self) -> Result<Extractor> {
+        let language = match self.language {
+            Some(language) => language,
+            None => bail!("a language is required to build an extractor"),
+        };
+
+        let mut queries = self.queries;
+        for raw in &self.query_strs {
+            queries.push(language.parse_query(raw).context("could not parse query")?);
+        }
+        if queries.is_empty() {
+            bail!("at least one query is required to build an extractor");
+        }
+
+        let mut extractor = Extractor::from_queries(language, queries);
+        if let Some(one_based) = self.one_based {
+            extractor = extractor.with_one_based(one_based);
+        }
+        if let Some(max_text_len) = self.max_text_len {
+            extractor = extractor.with_max_text_len(max_text_len);
+        }
+        if let Some(single_line_text) = self.single_line_text {
+            extractor = extractor.with_single_line_text(single_line_text);
+        }
+        if let Some(escaped_text) = self.escaped_text {
+            extractor = extractor.with_escaped_text(escaped_text);
+        }
+        if let Some(end_position) = self.end_position {
+            extractor = extractor.with_end_position(end_position);
+        }
+        if let Some(lossy_text) = self.lossy_text {
+            extractor = extractor.with_lossy_text(lossy_text);
+        }
+        if let Some(trim_cr) = self.trim_cr {
+            extractor = extractor.with_trim_cr(trim_cr);
+        }
+        if let Some(skip_text) = self.skip_text {
+            extractor = extractor.with_skip_text(skip_text);
+        }
+        if let Some(ignore) = self.ignore_underscore_captures {
+            extractor = extractor.with_ignore_underscore_captures(ignore);
+        }
+        if let Some(timeout) = self.timeout {
+            extractor = extractor.with_timeout(timeout);
+        }
+        if let Some(names) = &self.capture_filter {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            extractor = extractor.with_capture_filter(&names);
+        }
+        if let Some((regex, capture)) = self.text_filter {
+            extractor = extractor.with_text_filter(regex, capture.as_deref());
+        }
+        if let Some(max_matches) = self.max_matches {
+            extractor = extractor.with_max_matches(max_matches);
+        }
+        if let Some(match_limit) = self.match_limit {
+            extractor = extractor.with_match_limit(match_limit);
+        }
+        if let Some(name) = &self.label_capture {
+            extractor = extractor.with_label_capture(name);
+        }
+        if let Some(first_capture_only) = self.first_capture_only {
+            extractor = extractor.with_first_capture_only(first_capture_only);
+        }
+        if let Some(normalize_paths) = self.normalize_paths {
+            extractor = extractor.with_normalized_paths(normalize_paths);
+        }
+        if let Some(base_dir) = self.base_dir {
+            extractor = extractor.with_base_dir(base_dir);
+        }
+        #[cfg(feature = "mmap")]
+        if let Some(mmap) = self.mmap {
+            extractor = extractor.with_mmap(mmap);
+        }
+        #[cfg(feature = "encoding")]
+        if let Some(encoding) = self.encoding {
+            extractor = extractor.with_encoding(encoding);
+        }
+
+        Ok(extractor)
+    }
+}
@@ -1,6 +1,7 @@
+use crate::query::language;
 use crate::query::Extractor;
 use anyhow::{bail, Context, Result};
-use ignore::types::{Types, TypesBuilder};
+use ignore::types::Types;
 use ignore::DirEntry;
 use std::collections::HashMap;
 
@@ -15,13 +16,15 @@ pub struct ExtractorChooser<'extractor> {
 impl<'extractor> ExtractorChooser<'extractor> {
     /// Build a filetype matcher using provided extractors
     pub fn from_extractors(extractors: &[Extractor]) -> Result<ExtractorChooser> {
-        let mut types_builder = TypesBuilder::new();
-        types_builder.add_defaults();
+        let mut types_builder = language::types_builder()?;
 
         let mut names_to_extractors = HashMap::with_capacity(extractors.len());
 
         for extractor in extractors {
-            let name = extractor.language().name_for_types_builder();
+            let name = match extractor.known_language() {
+                Some(language) => language.name_for_types_builder(),
+                None => bail!("an extractor built from a raw grammar can't be chosen by file type"),
+            };
             types_builder.select(name);
 
             // a little reminder: insert returns the old value if the key was
@@ -1,6 +1,6 @@
 use anyhow::{Context, Result};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// A set of source files
 pub struct Files {
@@ -64,3 +64,53 @@ impl Iterator for Files {
         }
     }
 }
+
+/// Options for walking a directory tree
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// Whether honor .gitignore, .ignore and global git excludes or not
+    pub respect_gitignore: bool,
+    /// Whether visit hidden files and directories or not
+    pub hidden: bool,
+    /// Whether follow symbolic links or not. Symlink loops are reported as errors instead of being walked forever.
+    pub follow_links: bool,
+    /// Whether visit the entries of each directory sorted by file name or not.
+    /// Otherwise the order is the one of the filesystem, which varies between
+    /// platforms, and so does the order of batch results.
+    pub sort_by_path: bool,
+}
+
+impl Default for WalkOptions {
+    /// Skip hidden files and honor gitignore, as ripgrep does, visiting files in a reproducible order
+    fn default() -> WalkOptions {
+        WalkOptions {
+            respect_gitignore: true,
+            hidden: false,
+            follow_links: false,
+            sort_by_path: true,
+        }
+    }
+}
+
+impl WalkOptions {
+    /// Build a walker over `root` using the options
+    pub fn walker(&self, root: &Path) -> ignore::Walk {
+        let mut builder = ignore::WalkBuilder::new(root);
+        builder
+            .hidden(!self.hidden)
+            .parents(self.respect_gitignore)
+            .ignore(self.respect_gitignore)
+            .git_ignore(self.respect_gitignore)
+            .git_global(self.respect_gitignore)
+            .git_exclude(self.respect_gitignore)
+            .follow_links(self.follow_links);
+
+        // a depth-first walk with sorted siblings visits the paths sorted
+        // component by component, so batch results come out by path
+        if self.sort_by_path {
+            builder.sort_by_file_name(|a, b| a.cmp(b));
+        }
+
+        builder.build()
+    }
+}
@@ -0,0 +1,170 @@
+use crate::query::{ExtractedFile, ExtractedMatch};
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::str::FromStr;
+
+/// Template for printing one line per match
+///
+/// Placeholders are written in braces: `{file}`, `{start_row}`, `{start_col}`,
+/// `{end_row}`, `{end_col}`, `{name}`, `{kind}`, `{text}` and `{raw}`. Use
+/// `{{` and `}}` for literal braces. `{text}` is the text as `Display` shows
+/// it, following the label and the text settings of the Extractor
+/// (`with_single_line_text`, `with_max_text_len`...), while `{raw}` is the
+/// captured text as it is. The default `Display` output is the same as the
+/// template `{file}:{start_row}:{start_col}:{name}:{text}`.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{FormatTemplate,Language,Extractor};
+/// use tree_sitter::Parser;
+///
+/// let lang = Language::Rust;
+/// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+/// let extractor = Extractor::new(lang, query).with_max_text_len(2);
+/// let extracted = extractor
+///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+///     .unwrap();
+///
+/// let template = FormatTemplate::parse("{file}\t{start_row}\t{kind}\t{text}\t{raw}")?;
+/// let mut out = Vec::new();
+/// template.write(&extracted, &mut out)?;
+/// assert_eq!(String::from_utf8(out)?, "NO FILE\t1\tidentifier\tma…\tmain\n");
+///
+/// assert!(FormatTemplate::parse("{file}:{line}").is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatTemplate {
+    parts: Vec<Part>,
+}
+
+/// Piece of a `FormatTemplate`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// Field of a match that can be printed by a `FormatTemplate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    File,
+    StartRow,
+    StartCol,
+    EndRow,
+    EndCol,
+    Name,
+    Kind,
+    Text,
+    Raw,
+}
+
+impl FromStr for Placeholder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "file" => Ok(Placeholder::File),
+            "start_row" => Ok(Placeholder::StartRow),
+            "start_col" => Ok(Placeholder::StartCol),
+            "end_row" => Ok(Placeholder::EndRow),
+            "end_col" => Ok(Placeholder::EndCol),
+            "name" => Ok(Placeholder::Name),
+            "kind" => Ok(Placeholder::Kind),
+            "text" => Ok(Placeholder::Text),
+            "raw" => Ok(Placeholder::Raw),
+            _ => bail!(
+                "unknown placeholder {{{}}}. Try one of: {{file}}, {{start_row}}, {{start_col}}, {{end_row}}, {{end_col}}, {{name}}, {{kind}}, {{text}}, {{raw}}",
+                s
+            ),
+        }
+    }
+}
+
+impl FormatTemplate {
+    /// Parse a template, failing on unknown placeholders and unbalanced braces
+    pub fn parse(raw: &str) -> Result<FormatTemplate> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = raw.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => bail!("unclosed placeholder {{{} in format template", name),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(
+                        Placeholder::from_str(&name).context("could not parse format template")?,
+                    ));
+                }
+                '}' => bail!("unmatched }} in format template. Use }}}} for a literal brace"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(FormatTemplate { parts })
+    }
+
+    /// Write one line per match of `file` following the template
+    pub fn write<W: Write>(&self, file: &ExtractedFile, mut out: W) -> Result<()> {
+        for extraction in &file.matches {
+            writeln!(out, "{}", self.format(file, extraction)).context("could not write lines")?;
+        }
+
+        Ok(())
+    }
+
+    /// Format one match of `file` following the template
+    pub fn format(&self, file: &ExtractedFile, extraction: &ExtractedMatch) -> String {
+        let mut line = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => line.push_str(literal),
+                Part::Placeholder(placeholder) => match placeholder {
+                    Placeholder::File => line.push_str(file.filename()),
+                    Placeholder::StartRow => {
+                        line.push_str(&(extraction.start.row + extraction.base()).to_string())
+                    }
+                    Placeholder::StartCol => {
+                        line.push_str(&(extraction.start.column + extraction.base()).to_string())
+                    }
+                    Placeholder::EndRow => {
+                        line.push_str(&(extraction.end.row + extraction.base()).to_string())
+                    }
+                    Placeholder::EndCol => {
+                        line.push_str(&(extraction.end.column + extraction.base()).to_string())
+                    }
+                    Placeholder::Name => line.push_str(&extraction.name),
+                    Placeholder::Kind => line.push_str(extraction.kind()),
+                    Placeholder::Text => line.push_str(&extraction.display_text()),
+                    Placeholder::Raw => line.push_str(&extraction.text),
+                },
+            }
+        }
+
+        line
+    }
+}
@@ -1,53 +1,369 @@
-use anyhow::{anyhow, bail, Error, Result};
-use std::fmt::{Display, Formatter};
+use anyhow::{bail, Context, Error, Result};
+use ignore::types::TypesBuilder;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use tree_sitter::{QueryError, QueryErrorKind};
 
 /// Language support of query
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum Language {
     Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    /// TypeScript with JSX, in `.tsx` files
+    Tsx,
+    Go,
+    /// C, including `.h` headers
+    ///
+    /// The preprocessor isn't run: directives are nodes of the tree like
+    /// `preproc_include` or `preproc_def`, macro invocations look like
+    /// function calls, and every branch of `#if` is parsed. Code that only
+    /// makes sense after macro expansion may have `ERROR` nodes.
+    C,
+    /// C++, with the same preprocessor caveats as `C`
+    Cpp,
 }
 
 impl Language {
     /// A set of language supported by query
-    pub fn all() -> Vec<Language> {
-        vec![Language::Rust]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::Language;
+    ///
+    /// let names: Vec<&str> = Language::all().iter().map(|lang| lang.name()).collect();
+    /// assert!(names.contains(&"rust"));
+    /// ```
+    pub fn all() -> &'static [Language] {
+        &[
+            Language::Rust,
+            Language::Python,
+            Language::JavaScript,
+            Language::TypeScript,
+            Language::Tsx,
+            Language::Go,
+            Language::C,
+            Language::Cpp,
+        ]
+    }
+
+    /// Get the canonical lowercase name of the language, as accepted by `from_str`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Tsx => "tsx",
+            Language::Go => "go",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+        }
     }
 
     /// Map language to tree_sitter
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Python;
+    /// let query = lang
+    ///     .parse_query("(function_definition name: (identifier) @name)")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"def greet(name):\n    print(name)\n", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// assert_eq!(extracted.file_type, "python");
+    /// assert_eq!(extracted.matches[0].text, "greet");
+    ///
+    /// let lang = Language::TypeScript;
+    /// let query = lang
+    ///     .parse_query("(method_definition name: (property_identifier) @method)")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(
+    ///         None,
+    ///         b"class Greeter { greet(name: string): void {} }",
+    ///         &mut Parser::new(),
+    ///     )?
+    ///     .unwrap();
+    /// assert_eq!(extracted.matches[0].text, "greet");
+    ///
+    /// let lang = Language::Go;
+    /// let query = lang
+    ///     .parse_query("(function_declaration name: (identifier) @func)")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"package main\n\nfunc main() {}\n", &mut Parser::new())?
+    ///     .unwrap();
+    /// assert_eq!(extracted.matches[0].text, "main");
+    ///
+    /// let lang = Language::C;
+    /// let query = lang
+    ///     .parse_query("(function_definition declarator: (function_declarator declarator: (identifier) @func))")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"#include <stdio.h>\nint main(void) { return 0; }\n", &mut Parser::new())?
+    ///     .unwrap();
+    /// assert_eq!(extracted.matches[0].text, "main");
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn language(&self) -> tree_sitter::Language {
         unsafe {
             match self {
                 Language::Rust => tree_sitter_rust(),
+                Language::Python => tree_sitter_python(),
+                Language::JavaScript => tree_sitter_javascript(),
+                Language::TypeScript => tree_sitter_typescript(),
+                Language::Tsx => tree_sitter_tsx(),
+                Language::Go => tree_sitter_go(),
+                Language::C => tree_sitter_c(),
+                Language::Cpp => tree_sitter_cpp(),
             }
         }
     }
 
+    /// Get the ABI version the bundled grammar was generated with, see `tree_sitter::Language::version`
+    ///
+    /// Node types and fields may change when a grammar is regenerated, which
+    /// makes queries silently match nothing. Tools shipping queries written
+    /// against a given grammar can compare this version before extracting
+    /// and warn about a mismatch. tree_sitter itself only loads grammars
+    /// between `tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION` and
+    /// `tree_sitter::LANGUAGE_VERSION`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::Language;
+    ///
+    /// let version = Language::Rust.grammar_version();
+    /// assert!(version >= tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION);
+    /// assert!(version <= tree_sitter::LANGUAGE_VERSION);
+    /// ```
+    pub fn grammar_version(&self) -> usize {
+        self.language().version()
+    }
+
     /// Use tree_sitter to extract syntax information of program
+    ///
+    /// A query which doesn't compile fails with a `QueryParseError`, telling
+    /// what is wrong (syntax, node type, field, capture or predicate) and
+    /// where, with the offending line of the query.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::{Language,QueryParseError};
+    /// use tree_sitter::QueryErrorKind;
+    ///
+    /// let err = Language::Rust.parse_query("(function_item (identifer))").unwrap_err();
+    /// let err = err.downcast_ref::<QueryParseError>().unwrap();
+    /// assert_eq!(err.kind, QueryErrorKind::NodeType);
+    /// assert_eq!(err.offset, 16);
+    /// assert_eq!(err.snippet, "(function_item (identifer))");
+    /// ```
     pub fn parse_query(&self, raw: &str) -> Result<tree_sitter::Query> {
-        tree_sitter::Query::new(self.language(), raw).map_err(|err| anyhow!("{}", err))
+        tree_sitter::Query::new(self.language(), raw)
+            .map_err(|err| QueryParseError::new(err, raw, None).into())
+    }
+
+    /// Read a query from a file (usually `.scm`) and parse it
+    ///
+    /// Parse errors are prefixed with the path of the file, and tell the line
+    /// and column of the offending pattern.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::Language;
+    /// use std::path::Path;
+    ///
+    /// let query = Language::Rust.parse_query_file(Path::new("queries/functions.scm"))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_query_file(&self, path: &Path) -> Result<tree_sitter::Query> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("could not read query file {}", path.display()))?;
+
+        tree_sitter::Query::new(self.language(), &raw)
+            .map_err(|err| QueryParseError::new(err, &raw, Some(path)).into())
+    }
+
+    /// Get the language of files with extension `ext` (without the leading dot)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::Language;
+    ///
+    /// assert_eq!(Language::from_extension("rs"), Some(Language::Rust));
+    /// assert_eq!(Language::from_extension("py"), Some(Language::Python));
+    /// assert_eq!(Language::from_extension("mjs"), Some(Language::JavaScript));
+    /// assert_eq!(Language::from_extension("tsx"), Some(Language::Tsx));
+    /// assert_eq!(Language::from_extension("go"), Some(Language::Go));
+    /// assert_eq!(Language::from_extension("h"), Some(Language::C));
+    /// assert_eq!(Language::from_extension("hpp"), Some(Language::Cpp));
+    /// assert_eq!(Language::from_extension("txt"), None);
+    /// ```
+    pub fn from_extension(ext: &str) -> Option<Language> {
+        match ext.to_ascii_lowercase().as_str() {
+            "rs" => Some(Language::Rust),
+            "py" | "pyi" => Some(Language::Python),
+            "js" | "mjs" | "cjs" | "jsx" => Some(Language::JavaScript),
+            "ts" | "mts" | "cts" => Some(Language::TypeScript),
+            "tsx" => Some(Language::Tsx),
+            "go" => Some(Language::Go),
+            // headers are ambiguous, C is the common denominator
+            "c" | "h" => Some(Language::C),
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Some(Language::Cpp),
+            _ => None,
+        }
+    }
+
+    /// Get the language of a source file from its extension
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::Language;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(Language::from_path(Path::new("src/lib.rs")), Some(Language::Rust));
+    /// assert_eq!(Language::from_path(Path::new("Makefile")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Language> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Language::from_extension)
     }
+
+    /// Get the language of a script from its shebang line, such as `#!/usr/bin/env python3`
+    ///
+    /// Both direct interpreter paths and `env` (with its options) are
+    /// understood, and version suffixes like `3.11` are ignored. Interpreters
+    /// of languages without a grammar here, like `bash`, give `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::Language;
+    ///
+    /// assert_eq!(Language::from_shebang("#!/usr/bin/env python3"), Some(Language::Python));
+    /// assert_eq!(Language::from_shebang("#!/usr/local/bin/node --harmony"), Some(Language::JavaScript));
+    /// assert_eq!(Language::from_shebang("#!/usr/bin/env -S rust-script"), Some(Language::Rust));
+    /// assert_eq!(Language::from_shebang("#!/bin/bash"), None);
+    /// assert_eq!(Language::from_shebang("import os"), None);
+    /// ```
+    pub fn from_shebang(first_line: &str) -> Option<Language> {
+        let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = Path::new(words.next()?).file_name()?.to_str()?;
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-'))?;
+        }
+
+        match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+            "python" | "pypy" => Some(Language::Python),
+            "node" | "nodejs" => Some(Language::JavaScript),
+            "ts-node" | "deno" => Some(Language::TypeScript),
+            "rust-script" | "run-cargo-script" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+
+    /// Get the language of a source file from its extension, or from its shebang line if it has no extension
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rust_hero::query::Language;
+    /// use std::path::Path;
+    ///
+    /// let script = b"#!/usr/bin/env python\nprint('hello')\n";
+    /// assert_eq!(Language::detect(Path::new("bin/hello"), script), Some(Language::Python));
+    /// assert_eq!(Language::detect(Path::new("hello.rs"), script), Some(Language::Rust));
+    /// ```
+    pub fn detect(path: &Path, source: &[u8]) -> Option<Language> {
+        if path.extension().is_some() {
+            return Language::from_path(path);
+        }
+
+        let first_line = source.split(|byte| *byte == b'\n').next()?;
+        Language::from_shebang(std::str::from_utf8(first_line).ok()?)
+    }
+
     /// Get the language of source file
     pub fn name_for_types_builder(&self) -> &str {
         match self {
             Language::Rust => "rust",
+            Language::Python => "py",
+            Language::JavaScript => "js",
+            Language::TypeScript => "ts",
+            Language::Tsx => "tsx",
+            Language::Go => "go",
+            Language::C => "c",
+            Language::Cpp => "cpp",
         }
     }
 }
 
+/// Build a filetype matcher builder knowing the file types of every language
+///
+/// ripgrep's `ts` file type also covers `.tsx` files, which have a grammar of
+/// their own, so they are given a `tsx` file type instead.
+pub(crate) fn types_builder() -> Result<TypesBuilder> {
+    let mut types_builder = TypesBuilder::new();
+    types_builder.add_defaults();
+
+    types_builder.clear("ts");
+    for glob in ["*.ts", "*.mts", "*.cts"] {
+        types_builder
+            .add("ts", glob)
+            .context("could not add a file type")?;
+    }
+    types_builder
+        .add("tsx", "*.tsx")
+        .context("could not add a file type")?;
+
+    Ok(types_builder)
+}
+
 impl FromStr for Language {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
         match s {
             "rust" => Ok(Language::Rust),
+            "python" => Ok(Language::Python),
+            "javascript" => Ok(Language::JavaScript),
+            "typescript" => Ok(Language::TypeScript),
+            "tsx" => Ok(Language::Tsx),
+            "go" => Ok(Language::Go),
+            "c" => Ok(Language::C),
+            "cpp" => Ok(Language::Cpp),
             _ => bail!(
                 "unknown language {}. Try one of: {}",
                 s,
                 Language::all()
-                    .into_iter()
-                    .map(|l| l.to_string())
-                    .collect::<Vec<String>>()
+                    .iter()
+                    .map(|l| l.name())
+                    .collect::<Vec<&str>>()
                     .join(", ")
             ),
         }
@@ -56,12 +372,82 @@ impl FromStr for Language {
 
 impl Display for Language {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        match self {
-            Language::Rust => f.write_str("rust"),
+        f.write_str(self.name())
+    }
+}
+
+/// Error of a query which doesn't compile, see `Language::parse_query`
+#[derive(Debug, PartialEq, Eq)]
+pub struct QueryParseError {
+    /// What is wrong with the query
+    pub kind: QueryErrorKind,
+    /// Zero-based line of the error in the query
+    pub row: usize,
+    /// Zero-based column (in bytes) of the error in its line
+    pub column: usize,
+    /// Byte offset of the error in the query
+    pub offset: usize,
+    /// The offending node type, field, capture or predicate, as told by tree_sitter
+    pub message: String,
+    /// The line of the query the error is on
+    pub snippet: String,
+    /// The query file, if the query was read from one
+    pub path: Option<PathBuf>,
+}
+
+impl QueryParseError {
+    /// Build a QueryParseError from the error of tree_sitter and the query it is about
+    pub(crate) fn new(err: QueryError, raw: &str, path: Option<&Path>) -> QueryParseError {
+        QueryParseError {
+            snippet: raw.lines().nth(err.row).unwrap_or_default().to_string(),
+            kind: err.kind,
+            row: err.row,
+            column: err.column,
+            offset: err.offset,
+            message: err.message,
+            path: path.map(|path| path.to_owned()),
         }
     }
 }
 
+impl Display for QueryParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}: ", path.display())?;
+        }
+
+        let description = match self.kind {
+            QueryErrorKind::NodeType => format!("Invalid node type {}", self.message),
+            QueryErrorKind::Field => format!("Invalid field name {}", self.message),
+            QueryErrorKind::Capture => format!("Invalid capture name {}", self.message),
+            QueryErrorKind::Predicate => format!("Invalid predicate: {}", self.message),
+            // tree_sitter gives the offending line itself as message for
+            // these, which we already show below
+            QueryErrorKind::Structure => "Impossible pattern".to_string(),
+            QueryErrorKind::Syntax => "Invalid syntax".to_string(),
+            QueryErrorKind::Language => return write!(f, "{}", self.message),
+        };
+
+        // columns count bytes, the caret has to count chars
+        let caret = self
+            .snippet
+            .get(..self.column)
+            .map_or(self.column, |before| before.chars().count());
+        write!(
+            f,
+            "Query error at {}:{} (byte {}). {}\n{}\n{}^",
+            self.row + 1,
+            self.column + 1,
+            self.offset,
+            description,
+            self.snippet,
+            " ".repeat(caret)
+        )
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,8 +458,8 @@ mod tests {
         // something that could be worked around but I don't think it is right
         // now. If it bothers you in the future, feel free to take a stab at it!
         Language::all()
-            .into_iter()
-            .for_each(|lang| assert_eq!(Language::from_str(&lang.to_string()).unwrap(), lang))
+            .iter()
+            .for_each(|lang| assert_eq!(Language::from_str(&lang.to_string()).unwrap(), *lang))
     }
 
     #[test]
@@ -87,15 +473,65 @@ mod tests {
         // this happened. This test is just here to make sure we take a slightly
         // friendlier approach for 2.0.
         assert_eq!(
-            String::from("Query error at 1:2. Invalid node type node_that_doesnt_exist"),
+            String::from(
+                "Query error at 1:2 (byte 1). Invalid node type node_that_doesnt_exist\n\
+                 (node_that_doesnt_exist)\n \
+                 ^"
+            ),
             Language::Rust
                 .parse_query("(node_that_doesnt_exist)")
                 .unwrap_err()
                 .to_string(),
         )
     }
+
+    #[test]
+    fn parse_query_file_problem() {
+        let path = std::env::temp_dir().join("rust_hero_parse_query_file_problem.scm");
+        fs::write(&path, "(function_item) @function\n(node_that_doesnt_exist)").unwrap();
+
+        assert_eq!(
+            format!(
+                "{}: Query error at 2:2 (byte 27). Invalid node type node_that_doesnt_exist\n\
+                 (node_that_doesnt_exist)\n \
+                 ^",
+                path.display()
+            ),
+            Language::Rust
+                .parse_query_file(&path)
+                .unwrap_err()
+                .to_string(),
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parse_query_syntax_problem() {
+        let err = Language::Rust
+            .parse_query("(function_item\n  (identifier) @id))")
+            .unwrap_err();
+        let err = err.downcast_ref::<QueryParseError>().unwrap();
+
+        assert_eq!(err.kind, QueryErrorKind::Syntax);
+        assert_eq!((err.row, err.column, err.offset), (1, 19, 34));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Query error at 2:20 (byte 34). Invalid syntax\n  (identifier) @id))\n{}^",
+                " ".repeat(19)
+            )
+        );
+    }
 }
 
 extern "C" {
     fn tree_sitter_rust() -> tree_sitter::Language;
+    fn tree_sitter_python() -> tree_sitter::Language;
+    fn tree_sitter_javascript() -> tree_sitter::Language;
+    fn tree_sitter_typescript() -> tree_sitter::Language;
+    fn tree_sitter_tsx() -> tree_sitter::Language;
+    fn tree_sitter_go() -> tree_sitter::Language;
+    fn tree_sitter_c() -> tree_sitter::Language;
+    fn tree_sitter_cpp() -> tree_sitter::Language;
 }
@@ -0,0 +1,209 @@
+use crate::query::ExtractedFile;
+use anyhow::{bail, Context, Result};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+use std::env;
+use std::path::{Component, Path};
+use tree_sitter::Point;
+use url::Url;
+
+/// Position in a text document as LSP has it, zero-based
+///
+/// `character` counts bytes like tree_sitter does, which is the `utf-8`
+/// position encoding of LSP 3.17. Clients only speaking UTF-16 need the
+/// source line to convert it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LspPosition {
+    /// Zero-based line
+    pub line: usize,
+    /// Zero-based byte offset in the line
+    pub character: usize,
+}
+
+impl From<Point> for LspPosition {
+    fn from(point: Point) -> LspPosition {
+        LspPosition {
+            line: point.row,
+            character: point.column,
+        }
+    }
+}
+
+/// Range in a text document as LSP has it, end excluded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LspRange {
+    pub start: LspPosition,
+    pub end: LspPosition,
+}
+
+/// Location of a match as LSP has it, see `ExtractedFile::to_lsp_locations`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LspLocation {
+    /// `file://` URI of the file
+    pub uri: String,
+    pub range: LspRange,
+}
+
+impl<'query> ExtractedFile<'query> {
+    /// Get the locations of the matches in the shape of LSP `Location`s
+    ///
+    /// Lines and characters are zero-based whatever `Extractor::with_one_based`
+    /// says, see `LspPosition`. LSP clients only take absolute `file://`
+    /// URIs, so a relative path (as `Extractor::with_base_dir` makes them)
+    /// is resolved against `base_dir`, or the current directory without it.
+    /// A source without a path has no URI, which is an error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(Some(Path::new("src/main.rs")), b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// # #[cfg(unix)] {
+    /// let locations = extracted.to_lsp_locations(Some(Path::new("/home/me/project")))?;
+    /// assert_eq!(
+    ///     serde_json::to_string(&locations[0])?,
+    ///     r#"{"uri":"file:///home/me/project/src/main.rs","range":{"start":{"line":0,"character":3},"end":{"line":0,"character":7}}}"#
+    /// );
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_lsp_locations(&self, base_dir: Option<&Path>) -> Result<Vec<LspLocation>> {
+        let path = match &self.file {
+            Some(path) => path,
+            None => bail!("could not make a URI for a source without a path"),
+        };
+        let path = if path.is_absolute() {
+            path.clone()
+        } else {
+            let base_dir = match base_dir {
+                Some(base_dir) if base_dir.is_absolute() => base_dir.to_owned(),
+                Some(base_dir) => env::current_dir()
+                    .context("could not resolve the base directory")?
+                    .join(base_dir),
+                None => env::current_dir().context("could not resolve the path")?,
+            };
+            base_dir.join(path)
+        };
+        let uri = path_uri(&path);
+
+        Ok(self
+            .matches
+            .iter()
+            .map(|extraction| LspLocation {
+                uri: uri.clone(),
+                range: LspRange {
+                    start: extraction.start.into(),
+                    end: extraction.end.into(),
+                },
+            })
+            .collect())
+    }
+}
+
+/// Get the URI of a path, percent-encoded
+///
+/// An absolute path gives a `file://` URI (`file:///C:/...` on Windows), a
+/// relative path gives a relative reference with `/` separators.
+pub(crate) fn path_uri(path: &Path) -> String {
+    if let Ok(uri) = Url::from_file_path(path) {
+        return uri.into();
+    }
+
+    // `.` and `..` are kept as they are, so the reference still points
+    // at the same file
+    path.components()
+        .map(|component| match component {
+            Component::Normal(name) => {
+                utf8_percent_encode(&name.to_string_lossy(), PATH_SEGMENT).to_string()
+            }
+            other => other.as_os_str().to_string_lossy().into_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Characters percent-encoded in a path segment, as the url crate does
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{Extractor, Language};
+    use tree_sitter::Parser;
+
+    #[cfg(unix)]
+    #[test]
+    fn lsp_locations_of_relative_paths_with_spaces() {
+        let lang = Language::Rust;
+        let query = lang
+            .parse_query("(function_item (identifier) @id)")
+            .unwrap();
+        let extractor = Extractor::new(lang, query);
+        let extracted = extractor
+            .extract_from_text(
+                Some(Path::new("src/my main.rs")),
+                b"fn main(){}",
+                &mut Parser::new(),
+            )
+            .unwrap()
+            .unwrap();
+
+        let locations = extracted
+            .to_lsp_locations(Some(Path::new("/home/me/my project")))
+            .unwrap();
+        assert_eq!(
+            locations[0].uri,
+            "file:///home/me/my%20project/src/my%20main.rs"
+        );
+
+        let extracted = extractor
+            .extract_from_text(None, b"fn main(){}", &mut Parser::new())
+            .unwrap()
+            .unwrap();
+        assert!(extracted.to_lsp_locations(None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_uri_is_encoded() {
+        assert_eq!(
+            path_uri(Path::new("/home/me/my project/#1%.rs")),
+            "file:///home/me/my%20project/%231%25.rs"
+        );
+        assert_eq!(path_uri(Path::new("src/my file.rs")), "src/my%20file.rs");
+        assert_eq!(path_uri(Path::new("../lib/my x.rs")), "../lib/my%20x.rs");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn path_uri_is_encoded() {
+        assert_eq!(
+            path_uri(Path::new(r"C:\Users\me\my project\main.rs")),
+            "file:///C:/Users/me/my%20project/main.rs"
+        );
+        assert_eq!(path_uri(Path::new(r"src\my file.rs")), "src/my%20file.rs");
+        assert_eq!(path_uri(Path::new("../lib/my x.rs")), "../lib/my%20x.rs");
+    }
+}
@@ -34,13 +34,46 @@
 //! # }
 //! ```
 mod cli;
+#[cfg(feature = "encoding")]
+mod encoding;
 mod extractor;
+mod extractor_builder;
 mod extractor_chooser;
 mod files;
+mod format;
 mod language;
+mod lsp;
+mod multi_extractor;
+mod outline;
+mod output;
+mod parser_pool;
+mod query_cache;
+mod stats;
+#[cfg(feature = "watch")]
+mod watch;
 
 pub use cli::{Invocation, QueryFormat, QueryOpts};
-pub use extractor::{ExtractedFile, ExtractedMatch, Extractor};
+#[cfg(feature = "encoding")]
+pub use encoding::SourceEncoding;
+pub use extractor::{
+    BorrowedMatch, DedupKey, ExtractedFile, ExtractedMatch, Extractor, MatchNode, ParseTimeout,
+    QueryInfo,
+};
+pub use extractor_builder::ExtractorBuilder;
 pub use extractor_chooser::ExtractorChooser;
-pub use files::Files;
-pub use language::Language;
+pub use files::{Files, WalkOptions};
+pub use format::FormatTemplate;
+pub use language::{Language, QueryParseError};
+pub use lsp::{LspLocation, LspPosition, LspRange};
+pub use multi_extractor::MultiExtractor;
+pub use outline::OutlineEntry;
+#[cfg(feature = "schema")]
+pub use output::json_schema;
+pub use output::{
+    to_json, to_sarif, unified_diff, write_csv, write_github_annotations, AnnotationLevel,
+};
+pub use parser_pool::ParserPool;
+pub use query_cache::QueryCache;
+pub use stats::Stats;
+#[cfg(feature = "watch")]
+pub use watch::{watch, WatchEvent};
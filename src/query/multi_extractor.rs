@@ -0,0 +1,159 @@
+use crate::query::{ExtractedFile, Extractor, Language, WalkOptions};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tree_sitter::Parser;
+
+/// A set of Extractors, one per language, applied to each file according to its language
+///
+/// The language of a file is detected from its extension, or from its
+/// shebang line when it has none (see `Language::detect`), so a polyglot
+/// repository can be walked in one go.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{Language,Extractor,MultiExtractor};
+///
+/// let rust = Language::Rust;
+/// let python = Language::Python;
+/// let extractors = MultiExtractor::from_extractors(vec![
+///     Extractor::new(rust, rust.parse_query("(function_item (identifier) @id)")?),
+///     Extractor::new(python, python.parse_query("(function_definition (identifier) @id)")?),
+/// ])?;
+///
+/// assert!(extractors.extractor_for_language(Language::Python).is_some());
+/// assert!(extractors.extractor_for_language(Language::Go).is_none());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MultiExtractor {
+    /// Extractor of each language
+    extractors: HashMap<Language, Extractor>,
+}
+
+impl MultiExtractor {
+    /// Build a MultiExtractor from Extractors of different languages
+    ///
+    /// Extractors built `from_raw` have no language to be chosen by, and two
+    /// Extractors of the same language are ambiguous; both are errors.
+    pub fn from_extractors(extractors: Vec<Extractor>) -> Result<MultiExtractor> {
+        let mut by_language = HashMap::with_capacity(extractors.len());
+        for extractor in extractors {
+            let language = match extractor.known_language() {
+                Some(language) => *language,
+                None => bail!("an extractor built from a raw grammar can't be chosen by language"),
+            };
+
+            // a little reminder: insert returns the old value if the key was
+            // already present
+            if by_language.insert(language, extractor).is_some() {
+                bail!("got two extractors for {}", language);
+            }
+        }
+
+        Ok(MultiExtractor {
+            extractors: by_language,
+        })
+    }
+
+    /// Get the Extractor of `language`, if any
+    pub fn extractor_for_language(&self, language: Language) -> Option<&Extractor> {
+        self.extractors.get(&language)
+    }
+
+    /// Extracted query information from one source file, with the Extractor of its language
+    ///
+    /// # Returns
+    ///
+    /// * `None` if there is no Extractor for the language of the file (or
+    ///   its language is unknown), or the file has no match
+    pub fn extract_from_file(
+        &self,
+        path: &Path,
+        parser: &mut Parser,
+    ) -> Result<Option<ExtractedFile>> {
+        // files with an extension don't need to be read to be told apart
+        if path.extension().is_some() {
+            return match Language::from_path(path).and_then(|l| self.extractors.get(&l)) {
+                Some(extractor) => extractor.extract_from_file(path, parser),
+                None => Ok(None),
+            };
+        }
+
+        let source = fs::read(path).context("could not read file")?;
+        match Language::detect(path, &source).and_then(|l| self.extractors.get(&l)) {
+            Some(extractor) => extractor.extract_from_text(Some(path), &source, parser),
+            None => Ok(None),
+        }
+    }
+
+    /// Extracted query information from every source file under a directory
+    ///
+    /// Every file visited is given to `extract_from_file`, so files of
+    /// languages without an Extractor are skipped. A file that can't be read
+    /// or parsed doesn't abort the walk; its error is collected in place of
+    /// its result.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor,MultiExtractor,WalkOptions};
+    /// use std::path::Path;
+    /// use tree_sitter::Parser;
+    ///
+    /// let rust = Language::Rust;
+    /// let python = Language::Python;
+    /// let extractors = MultiExtractor::from_extractors(vec![
+    ///     Extractor::new(rust, rust.parse_query("(function_item (identifier) @id)")?),
+    ///     Extractor::new(python, python.parse_query("(function_definition (identifier) @id)")?),
+    /// ])?;
+    /// for result in extractors.extract_from_dir(Path::new("."), &WalkOptions::default(), &mut Parser::new())? {
+    ///     match result {
+    ///         Ok(extracted) => print!("{}", extracted),
+    ///         Err(err) => eprintln!("{:?}", err),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn extract_from_dir(
+        &self,
+        root: &Path,
+        walk: &WalkOptions,
+        parser: &mut Parser,
+    ) -> Result<Vec<Result<ExtractedFile>>> {
+        fs::metadata(root).with_context(|| format!("couldn't read {}", root.display()))?;
+
+        let mut out = Vec::new();
+        for entry in walk.walker(root) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    out.push(Err(err).context("couldn't walk the directory tree"));
+                    continue;
+                }
+            };
+
+            if !entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
+                continue;
+            }
+
+            let path = entry.path();
+            match self
+                .extract_from_file(path, parser)
+                .with_context(|| format!("could not extract matches from {}", path.display()))
+            {
+                Ok(Some(extracted)) => out.push(Ok(extracted)),
+                Ok(None) => (),
+                Err(err) => out.push(Err(err)),
+            }
+        }
+
+        Ok(out)
+    }
+}
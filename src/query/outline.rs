@@ -0,0 +1,105 @@
+use crate::query::{ExtractedFile, MatchNode};
+use serde::Serialize;
+
+/// Entry of a symbol outline, see `ExtractedFile::outline`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutlineEntry {
+    /// Number of declarations the entry is nested in, zero at the top level
+    pub depth: usize,
+    /// Node kind of the declaration, such as `function_item`
+    pub kind: String,
+    /// Text of the name of the declaration
+    pub name: String,
+    /// One-based line the declaration starts on
+    pub line: usize,
+}
+
+impl<'query> ExtractedFile<'query> {
+    /// Build a symbol outline of the matches, like the symbol list of an editor
+    ///
+    /// The matches are nested with `as_tree`. A match is a declaration when
+    /// it has a label (see `Extractor::with_label_capture`), or else when one
+    /// of the matches directly nested in it is in its `name` field, which
+    /// gives the name of the entry; its other nested matches are one level
+    /// deeper. Matches without a name are left out of the outline, but the
+    /// declarations they contain are not. Lines are one-based whatever
+    /// `Extractor::with_one_based` says.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let lang = Language::Rust;
+    /// let query = lang
+    ///     .parse_query("(mod_item name: (_) @name) @module (function_item name: (_) @name) @function")
+    ///     .unwrap();
+    /// let extractor = Extractor::new(lang, query);
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"mod a {\n    fn b() {}\n}\nfn c() {}", &mut Parser::new())?
+    ///     .unwrap();
+    ///
+    /// let outline: Vec<_> = extracted
+    ///     .outline()
+    ///     .into_iter()
+    ///     .map(|entry| format!("{}{} {} ({})", "  ".repeat(entry.depth), entry.kind, entry.name, entry.line))
+    ///     .collect();
+    /// assert_eq!(outline, vec!["mod_item a (1)", "  function_item b (2)", "function_item c (4)"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        let mut entries = Vec::new();
+        for root in &self.as_tree() {
+            push_entries(root, 0, &mut entries);
+        }
+        entries
+    }
+}
+
+/// Push the outline entries of `node` and of the matches it contains
+fn push_entries(node: &MatchNode, depth: usize, entries: &mut Vec<OutlineEntry>) {
+    let extraction = node.extraction;
+    // the nested match giving the name is part of the entry, not a child of it
+    let (name, name_child) = match &extraction.label {
+        Some(label) => (
+            Some(label.clone()),
+            node.children.iter().position(|child| {
+                (child.extraction.query_index, child.extraction.match_index)
+                    == (extraction.query_index, extraction.match_index)
+                    && &child.extraction.text == label
+            }),
+        ),
+        None => {
+            let index = node
+                .children
+                .iter()
+                .position(|child| child.extraction.field_name.as_deref() == Some("name"));
+            (
+                index.map(|index| node.children[index].extraction.text.clone()),
+                index,
+            )
+        }
+    };
+
+    let depth = match name {
+        Some(name) => {
+            entries.push(OutlineEntry {
+                depth,
+                kind: extraction.kind().to_string(),
+                name,
+                line: extraction.start.row + 1,
+            });
+            depth + 1
+        }
+        None => depth,
+    };
+
+    for (index, child) in node.children.iter().enumerate() {
+        if Some(index) != name_child {
+            push_entries(child, depth, entries);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use crate::query::lsp::path_uri;
+use crate::query::ExtractedFile;
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use similar::TextDiff;
+use std::io::Write;
+use std::path::Path;
+
+/// Write extracted files as CSV, one record per match
+///
+/// The header is `file,language,capture_name,kind,start_row,start_col,end_row,end_col,text`.
+/// Text containing commas, quotes or newlines is quoted as RFC 4180 requires.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{write_csv,Language,Extractor};
+/// use tree_sitter::Parser;
+///
+/// let lang = Language::Rust;
+/// let query = lang.parse_query("(function_item) @function").unwrap();
+/// let extractor = Extractor::new(lang, query);
+/// let extracted = extractor
+///     .extract_from_text(None, b"fn main() {\n}", &mut Parser::new())?
+///     .unwrap();
+///
+/// let mut out = Vec::new();
+/// write_csv(&[extracted], &mut out)?;
+/// assert_eq!(
+///     String::from_utf8(out)?,
+///     "file,language,capture_name,kind,start_row,start_col,end_row,end_col,text\n\
+///      NO FILE,rust,function,function_item,1,1,2,2,\"fn main() {\n}\"\n"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_csv<W: Write>(files: &[ExtractedFile], out: W) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer
+        .write_record([
+            "file",
+            "language",
+            "capture_name",
+            "kind",
+            "start_row",
+            "start_col",
+            "end_row",
+            "end_col",
+            "text",
+        ])
+        .context("could not write CSV header")?;
+
+    for file in files {
+        for extraction in &file.matches {
+            writer
+                .write_record([
+                    file.filename(),
+                    &file.file_type,
+                    &extraction.name,
+                    extraction.kind(),
+                    &(extraction.start.row + extraction.base()).to_string(),
+                    &(extraction.start.column + extraction.base()).to_string(),
+                    &(extraction.end.row + extraction.base()).to_string(),
+                    &(extraction.end.column + extraction.base()).to_string(),
+                    &extraction.text,
+                ])
+                .context("could not write CSV record")?;
+        }
+    }
+
+    writer.flush().context("could not write CSV output")
+}
+
+/// Serialize extracted files to a JSON array, pretty-printed for humans or compact for pipelines
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{to_json,Language,Extractor};
+/// use tree_sitter::Parser;
+///
+/// let lang = Language::Rust;
+/// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+/// let extractor = Extractor::new(lang, query);
+/// let extracted = extractor
+///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+///     .unwrap();
+///
+/// let json = to_json(&[extracted], false)?;
+/// assert!(json.starts_with("[{"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn to_json(files: &[ExtractedFile], pretty: bool) -> Result<String> {
+    if pretty {
+        serde_json::to_string_pretty(files).context("could not serialize to JSON")
+    } else {
+        serde_json::to_string(files).context("could not serialize to JSON")
+    }
+}
+
+/// Build a SARIF 2.1.0 log of extracted files, one `result` per match
+///
+/// The capture name is the rule id and the captured text the message, so the
+/// log can be uploaded to GitHub code scanning or any SARIF-aware tool.
+/// SARIF lines and columns are both one-based whatever `Extractor::with_one_based`
+/// says, and columns count bytes like tree_sitter does. Artifact URIs are
+/// percent-encoded, `file://` URIs for absolute paths and relative
+/// references otherwise; matches of a source without a path have no
+/// artifact location.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{to_sarif,Language,Extractor};
+/// use std::path::Path;
+/// use tree_sitter::Parser;
+///
+/// let lang = Language::Rust;
+/// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+/// let extractor = Extractor::new(lang, query).with_one_based(false);
+/// let extracted = extractor
+///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+///     .unwrap();
+///
+/// let sarif = to_sarif(&[extracted], "rust_hero");
+/// let result = &sarif["runs"][0]["results"][0];
+/// assert_eq!(result["ruleId"], "id");
+/// assert_eq!(result["message"]["text"], "main");
+/// let location = &result["locations"][0]["physicalLocation"];
+/// assert!(location.get("artifactLocation").is_none());
+/// let region = &location["region"];
+/// assert_eq!((region["startLine"].as_u64(), region["startColumn"].as_u64()), (Some(1), Some(4)));
+///
+/// let extracted = extractor
+///     .extract_from_text(Some(Path::new("src/my main.rs")), b"fn main(){}", &mut Parser::new())?
+///     .unwrap();
+/// let sarif = to_sarif(&[extracted], "rust_hero");
+/// let location = &sarif["runs"][0]["results"][0]["locations"][0]["physicalLocation"];
+/// assert_eq!(location["artifactLocation"]["uri"], "src/my%20main.rs");
+/// # Ok(())
+/// # }
+/// ```
+pub fn to_sarif(files: &[ExtractedFile], tool_name: &str) -> Value {
+    let mut rules: Vec<&str> = Vec::new();
+    let mut results = Vec::new();
+
+    for file in files {
+        let uri = file.file.as_deref().map(path_uri);
+        for extraction in &file.matches {
+            if !rules.contains(&extraction.name.as_ref()) {
+                rules.push(&extraction.name);
+            }
+
+            let mut location = json!({
+                "region": {
+                    "startLine": extraction.start.row + 1,
+                    "startColumn": extraction.start.column + 1,
+                    "endLine": extraction.end.row + 1,
+                    "endColumn": extraction.end.column + 1,
+                },
+            });
+            if let Some(uri) = &uri {
+                location["artifactLocation"] = json!({ "uri": uri });
+            }
+
+            results.push(json!({
+                "ruleId": extraction.name,
+                "level": "note",
+                "message": { "text": extraction.text },
+                "locations": [{ "physicalLocation": location }],
+            }));
+        }
+    }
+
+    let rules: Vec<Value> = rules.into_iter().map(|id| json!({ "id": id })).collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": tool_name, "rules": rules } },
+            "results": results,
+        }],
+    })
+}
+
+/// Severity of a GitHub Actions workflow annotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationLevel {
+    Notice,
+    Warning,
+    Error,
+}
+
+impl AnnotationLevel {
+    /// Name of the workflow command
+    fn command(&self) -> &'static str {
+        match self {
+            AnnotationLevel::Notice => "notice",
+            AnnotationLevel::Warning => "warning",
+            AnnotationLevel::Error => "error",
+        }
+    }
+}
+
+/// Write extracted files as GitHub Actions workflow annotations, one per match
+///
+/// Every match becomes a `::notice file=...,line=...,col=...::message` line
+/// (or `::warning`/`::error` following `level`), which GitHub shows inline on
+/// pull requests. Lines and columns are one-based whatever
+/// `Extractor::with_one_based` says, and the message is the capture name and
+/// the first line of the text.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{write_github_annotations,AnnotationLevel,Language,Extractor};
+/// use std::path::Path;
+/// use tree_sitter::Parser;
+///
+/// let lang = Language::Rust;
+/// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+/// let extractor = Extractor::new(lang, query);
+/// let extracted = extractor
+///     .extract_from_text(Some(Path::new("src/main.rs")), b"fn main(){}", &mut Parser::new())?
+///     .unwrap();
+///
+/// let mut out = Vec::new();
+/// write_github_annotations(&[extracted], AnnotationLevel::Warning, &mut out)?;
+/// assert_eq!(
+///     String::from_utf8(out)?,
+///     "::warning file=src/main.rs,line=1,col=4::id: main\n"
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_github_annotations<W: Write>(
+    files: &[ExtractedFile],
+    level: AnnotationLevel,
+    mut out: W,
+) -> Result<()> {
+    for file in files {
+        let filename = escape_annotation_property(file.filename());
+        for extraction in &file.matches {
+            let message = format!(
+                "{}: {}",
+                extraction.name,
+                extraction.text.lines().next().unwrap_or_default()
+            );
+            writeln!(
+                out,
+                "::{} file={},line={},col={}::{}",
+                level.command(),
+                filename,
+                extraction.start.row + 1,
+                extraction.start.column + 1,
+                escape_annotation_data(&message)
+            )
+            .context("could not write annotations")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Escape the message of a workflow command
+fn escape_annotation_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape a property value of a workflow command, which can't hold `:` nor `,` either
+fn escape_annotation_property(property: &str) -> String {
+    escape_annotation_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+/// Build the JSON Schema of the output of `to_json`, an array of `ExtractedFile`
+///
+/// The schema is generated from the structs, so it can't drift from what is
+/// serialized. It has the `text_escaped` field of `Extractor::with_escaped_text`
+/// as optional, and the points (`start` and `end`) as serialized, one-based
+/// unless the Extractor was built `with_one_based(false)`. Only available
+/// with the `schema` feature.
+///
+/// # Example
+///
+/// ```
+/// use rust_hero::query::json_schema;
+///
+/// let schema = json_schema();
+/// assert_eq!(schema["type"], "array");
+/// let properties = &schema["definitions"]["ExtractedMatch"]["properties"];
+/// assert!(properties["text_escaped"].is_object());
+/// assert_eq!(schema["definitions"]["Point"]["required"], serde_json::json!(["column", "row"]));
+/// ```
+#[cfg(feature = "schema")]
+pub fn json_schema() -> Value {
+    let mut schema = serde_json::to_value(schemars::schema_for!(Vec<ExtractedFile>))
+        .expect("a JSON Schema is always valid JSON");
+
+    // only serialized when asked for, so it isn't a field of the struct
+    schema["definitions"]["ExtractedMatch"]["properties"]["text_escaped"] = json!({
+        "description": "Captured text with control characters escaped, see `Extractor::with_escaped_text`",
+        "type": "string",
+    });
+
+    schema
+}
+
+/// Build the unified diff turning `original` into `rewritten`, the source of `path`
+///
+/// Meant to preview the rewrites of `Extractor::rewrite` (a dry run) instead
+/// of writing them. The headers are `a/<path>` and `b/<path>` like git, with
+/// three lines of context around the changes. Nothing is returned when the
+/// sources are the same.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{unified_diff,Language,Extractor};
+/// use std::path::Path;
+/// use tree_sitter::Parser;
+///
+/// let source = "fn old(){}\n";
+/// let lang = Language::Rust;
+/// let query = lang.parse_query("(function_item (identifier) @id)").unwrap();
+/// let extractor = Extractor::new(lang, query);
+/// let rewritten = extractor.rewrite(source.as_bytes(), &mut Parser::new(), |_| {
+///     Some("new".to_string())
+/// })?;
+///
+/// assert_eq!(
+///     unified_diff(Path::new("src/lib.rs"), source, &rewritten),
+///     "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-fn old(){}\n+fn new(){}\n"
+/// );
+/// assert_eq!(unified_diff(Path::new("src/lib.rs"), source, source), "");
+/// # Ok(())
+/// # }
+/// ```
+pub fn unified_diff(path: &Path, original: &str, rewritten: &str) -> String {
+    if original == rewritten {
+        return String::new();
+    }
+
+    let path = path.display();
+    TextDiff::from_lines(original, rewritten)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
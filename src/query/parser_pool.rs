@@ -0,0 +1,46 @@
+use crate::query::Language;
+use anyhow::{Context, Result};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use tree_sitter::Parser;
+
+/// A set of reusable `Parser`s, one per language, configured once
+#[derive(Default)]
+pub struct ParserPool {
+    /// Configured parser for each language
+    parsers: HashMap<Language, Parser>,
+}
+
+impl ParserPool {
+    /// Build an empty ParserPool
+    pub fn new() -> ParserPool {
+        ParserPool::default()
+    }
+
+    /// Get the parser of `language`, creating and configuring it on first use
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,ParserPool};
+    ///
+    /// let mut pool = ParserPool::new();
+    /// let parser = pool.get(Language::Rust)?;
+    /// assert!(parser.parse("fn main(){}", None).is_some());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get(&mut self, language: Language) -> Result<&mut Parser> {
+        match self.parsers.entry(language) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let mut parser = Parser::new();
+                parser
+                    .set_language(language.language())
+                    .context("could not set language")?;
+                Ok(entry.insert(parser))
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::query::{Extractor, Language};
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// A set of Extractors built from raw queries, compiled once per language and query
+///
+/// Compiling a query is much more expensive than cloning an Extractor (the
+/// compiled queries are shared), so tools building the same Extractor over
+/// and over can keep a cache instead. See `Extractor::cached` for a
+/// thread-local one.
+#[derive(Debug, Default)]
+pub struct QueryCache {
+    /// Extractor of each language and raw query
+    extractors: HashMap<(Language, String), Extractor>,
+}
+
+impl QueryCache {
+    /// Build an empty QueryCache
+    pub fn new() -> QueryCache {
+        QueryCache::default()
+    }
+
+    /// Get the Extractor of `raw` in `language`, compiling the query on first use
+    ///
+    /// A query that doesn't compile isn't cached, its error is given every time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,QueryCache};
+    ///
+    /// let mut cache = QueryCache::new();
+    /// cache.get(Language::Rust, "(function_item) @function")?;
+    /// cache.get(Language::Rust, "(function_item) @function")?;
+    /// assert_eq!(cache.len(), 1);
+    ///
+    /// assert!(cache.get(Language::Rust, "(function_item").is_err());
+    /// assert_eq!(cache.len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get(&mut self, language: Language, raw: &str) -> Result<&Extractor> {
+        match self.extractors.entry((language, raw.to_string())) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let query = language.parse_query(raw).context("could not parse query")?;
+                Ok(entry.insert(Extractor::new(language, query)))
+            }
+        }
+    }
+
+    /// Get the number of cached Extractors
+    pub fn len(&self) -> usize {
+        self.extractors.len()
+    }
+
+    /// Tell whether no Extractor is cached
+    pub fn is_empty(&self) -> bool {
+        self.extractors.is_empty()
+    }
+
+    /// Drop every cached Extractor
+    pub fn clear(&mut self) {
+        self.extractors.clear()
+    }
+}
+
+thread_local! {
+    static CACHE: RefCell<QueryCache> = RefCell::new(QueryCache::new());
+}
+
+impl Extractor {
+    /// Build the Extractor of `raw` in `language` from a thread-local `QueryCache`
+    ///
+    /// The query is only compiled the first time it is asked for on a
+    /// thread; later calls clone the cached Extractor, which shares the
+    /// compiled query. Options set on the returned Extractor don't change the
+    /// cached one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> anyhow::Result<()> {
+    /// use rust_hero::query::{Language,Extractor};
+    /// use tree_sitter::Parser;
+    ///
+    /// let extractor = Extractor::cached(Language::Rust, "(function_item (identifier) @id)")?;
+    /// let extracted = extractor
+    ///     .extract_from_text(None, b"fn main(){}", &mut Parser::new())?
+    ///     .unwrap();
+    /// assert_eq!(extracted.matches[0].text, "main");
+    ///
+    /// Extractor::clear_cache();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cached(language: Language, raw: &str) -> Result<Extractor> {
+        CACHE.with(|cache| cache.borrow_mut().get(language, raw).cloned())
+    }
+
+    /// Drop every Extractor cached on this thread by `cached`
+    pub fn clear_cache() {
+        CACHE.with(|cache| cache.borrow_mut().clear())
+    }
+}
@@ -0,0 +1,67 @@
+use crate::query::ExtractedFile;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Aggregate counts over extracted files
+///
+/// Computed in a single pass over the matches, so it is cheap enough to
+/// report alongside every run.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{Language,Extractor,Stats};
+/// use tree_sitter::Parser;
+///
+/// let lang = Language::Rust;
+/// let query = lang
+///     .parse_query("(function_item (identifier) @id) @function")
+///     .unwrap();
+/// let extractor = Extractor::new(lang, query);
+/// let extracted = extractor
+///     .extract_from_text(None, b"fn a(){} fn b(){}", &mut Parser::new())?
+///     .unwrap();
+///
+/// let stats = Stats::from(&[extracted][..]);
+/// assert_eq!(stats.files, 1);
+/// assert_eq!(stats.matches, 4);
+/// assert_eq!(stats.by_capture["id"], 2);
+/// assert_eq!(stats.by_language["rust"], 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Stats {
+    /// Number of files processed
+    pub files: usize,
+    /// Total number of matches
+    pub matches: usize,
+    /// Number of matches per capture name
+    pub by_capture: BTreeMap<String, usize>,
+    /// Number of files per language
+    pub by_language: BTreeMap<String, usize>,
+}
+
+impl<'query> From<&[ExtractedFile<'query>]> for Stats {
+    fn from(files: &[ExtractedFile<'query>]) -> Stats {
+        let mut stats = Stats::default();
+
+        for file in files {
+            stats.files += 1;
+            stats.matches += file.matches.len();
+            *stats.by_language.entry(file.file_type.clone()).or_default() += 1;
+
+            for extraction in &file.matches {
+                match stats.by_capture.get_mut(extraction.name.as_ref()) {
+                    Some(count) => *count += 1,
+                    None => {
+                        stats.by_capture.insert(extraction.name.to_string(), 1);
+                    }
+                }
+            }
+        }
+
+        stats
+    }
+}
@@ -0,0 +1,166 @@
+use crate::query::{ExtractedFile, Extractor, Language, WalkOptions};
+use anyhow::{Context, Error, Result};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+use tree_sitter::Parser;
+
+/// How long file events are gathered before extracting, so a burst of writes
+/// (an editor saving, a `git checkout`) triggers one extraction per file
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Change of a watched source file, see `watch`
+#[derive(Debug)]
+pub enum WatchEvent<'query> {
+    /// The file was created or changed, with its fresh matches or `None` if it has none anymore
+    Changed(PathBuf, Option<ExtractedFile<'query>>),
+    /// The file was removed (or renamed away), so its previous matches are gone
+    Removed(PathBuf),
+    /// The file couldn't be extracted, or the watcher reported an error
+    Failed(PathBuf, Error),
+    /// The watcher lost events, so what was heard of the files may be stale.
+    /// A `Changed` event follows for every source file under the root;
+    /// files missing from them were removed meanwhile
+    Rescanned,
+}
+
+/// Watch a directory and extract again every source file that changes
+///
+/// Blocks the current thread: file events are debounced, then `callback` is
+/// called once per changed or removed source file of the language of
+/// `extractor`. Watching stops, returning the error, as soon as `callback`
+/// fails.
+///
+/// When the watcher loses events (the inotify queue overflowing, say), the
+/// callback gets `WatchEvent::Rescanned` and then fresh matches of every
+/// source file under `root`, so it can resync.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// use rust_hero::query::{watch,Language,Extractor,WatchEvent};
+/// use std::path::Path;
+///
+/// let lang = Language::Rust;
+/// let query = lang
+///     .parse_query("(function_item (identifier) @id) @function")
+///     .unwrap();
+/// let extractor = Extractor::new(lang, query);
+/// watch(&extractor, Path::new("src"), |event| {
+///     match event {
+///         WatchEvent::Changed(_, Some(extracted)) => print!("{}", extracted),
+///         WatchEvent::Changed(path, None) | WatchEvent::Removed(path) => {
+///             eprintln!("no more matches in {}", path.display())
+///         }
+///         WatchEvent::Failed(path, err) => eprintln!("{}: {:?}", path.display(), err),
+///         WatchEvent::Rescanned => eprintln!("events were lost, extracting everything again"),
+///     }
+///     Ok(())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn watch<'query, F>(extractor: &'query Extractor, root: &Path, mut callback: F) -> Result<()>
+where
+    F: FnMut(WatchEvent<'query>) -> Result<()>,
+{
+    let (tx, rx) = channel();
+    let mut watcher = watcher(tx, DEBOUNCE).context("could not start watching")?;
+    watcher
+        .watch(root, RecursiveMode::Recursive)
+        .with_context(|| format!("could not watch {}", root.display()))?;
+
+    let mut parser = Parser::new();
+
+    // the loop ends when the watcher hangs up
+    for event in rx {
+        match event {
+            DebouncedEvent::Create(path) | DebouncedEvent::Write(path)
+                if is_source(extractor, &path) =>
+            {
+                callback(extract(extractor, path, &mut parser))?
+            }
+            DebouncedEvent::Remove(path) if is_source(extractor, &path) => {
+                callback(WatchEvent::Removed(path))?
+            }
+            DebouncedEvent::Rename(from, to) => {
+                if is_source(extractor, &from) {
+                    callback(WatchEvent::Removed(from))?;
+                }
+                if is_source(extractor, &to) {
+                    callback(extract(extractor, to, &mut parser))?;
+                }
+            }
+            DebouncedEvent::Error(err, path) => callback(WatchEvent::Failed(
+                path.unwrap_or_else(|| root.to_owned()),
+                Error::new(err).context("watcher failed"),
+            ))?,
+            DebouncedEvent::Rescan => {
+                callback(WatchEvent::Rescanned)?;
+                rescan(extractor, root, &mut parser, &mut callback)?
+            }
+            // notices come before the debounced events, and chmod doesn't
+            // change the source
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `path` is a source file of the language of `extractor`
+fn is_source(extractor: &Extractor, path: &Path) -> bool {
+    extractor.known_language().is_some()
+        && Language::from_path(path) == extractor.known_language().copied()
+}
+
+/// Extract again from every source file under `root`, after the watcher lost events
+fn rescan<'query, F>(
+    extractor: &'query Extractor,
+    root: &Path,
+    parser: &mut Parser,
+    callback: &mut F,
+) -> Result<()>
+where
+    F: FnMut(WatchEvent<'query>) -> Result<()>,
+{
+    // the watcher sees hidden and ignored files too
+    let walk = WalkOptions {
+        respect_gitignore: false,
+        hidden: true,
+        ..WalkOptions::default()
+    };
+    for entry in walk.walker(root) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                callback(WatchEvent::Failed(
+                    root.to_owned(),
+                    Error::new(err).context("couldn't walk the directory tree"),
+                ))?;
+                continue;
+            }
+        };
+
+        let is_file = entry.file_type().map(|ft| ft.is_file()).unwrap_or(false);
+        if is_file && is_source(extractor, entry.path()) {
+            callback(extract(extractor, entry.into_path(), parser))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract from a changed file, turning the result into its event
+fn extract<'query>(
+    extractor: &'query Extractor,
+    path: PathBuf,
+    parser: &mut Parser,
+) -> WatchEvent<'query> {
+    match extractor.extract_from_file(&path, parser) {
+        Ok(extracted) => WatchEvent::Changed(path, extracted),
+        Err(err) => WatchEvent::Failed(path, err),
+    }
+}
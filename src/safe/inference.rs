@@ -519,13 +519,7 @@ impl SafeLanguageModel {
             }
             QueryFormat::JsonLines => {
                 for extracted_file in extracted_files {
-                    writeln!(
-                        out,
-                        "{}",
-                        serde_json::to_string(&extracted_file)
-                            .context("could not write JSON output")?
-                    )
-                    .context("could not write line")?;
+                    extracted_file.write_ndjson(&mut out)?;
                 }
             }
 
@@ -533,6 +527,12 @@ impl SafeLanguageModel {
                 serde_json::to_writer_pretty(out, &extracted_files)
                     .context("could not write JSON output")?;
             }
+
+            QueryFormat::Quickfix => {
+                for extracted_file in extracted_files {
+                    extracted_file.write_quickfix(&mut out)?;
+                }
+            }
         }
 
         Ok(())